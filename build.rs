@@ -16,9 +16,13 @@ fn main() {
             "edit-regular",
             "document-one-page-regular",
             "image-regular",
+            "layout-column-two-regular",
             "folder-regular",
             "menu",
             "search-regular",
+            "tag-regular",
+            "zoom-in-regular",
+            "zoom-out-regular",
         ],
     );
 }
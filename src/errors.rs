@@ -2,22 +2,156 @@ use std::{fmt, string::FromUtf8Error};
 
 use gtk::glib;
 
+/// A storage operation, used to tell the user what went wrong.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    OpenStorage,
+    ListItems,
+    CreateNote,
+    RenameNote,
+    CreateCollection,
+    RenameCollection,
+    DeleteCollection,
+    DeleteItem,
+    RestoreItem,
+    MoveItem,
+    CopyItem,
+    LoadContent,
+    LoadBytes,
+    SaveContent,
+    Search,
+    Watch,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let description = match self {
+            Operation::OpenStorage => "open notebook",
+            Operation::ListItems => "list",
+            Operation::CreateNote => "create note",
+            Operation::RenameNote => "rename note",
+            Operation::CreateCollection => "create folder",
+            Operation::RenameCollection => "rename folder",
+            Operation::DeleteCollection => "delete folder",
+            Operation::DeleteItem => "delete",
+            Operation::RestoreItem => "restore",
+            Operation::MoveItem => "move",
+            Operation::CopyItem => "copy",
+            Operation::LoadContent => "load note",
+            Operation::LoadBytes => "load file",
+            Operation::SaveContent => "save note",
+            Operation::Search => "search",
+            Operation::Watch => "watch",
+        };
+
+        write!(f, "{}", description)
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
-    DecodeError(FromUtf8Error),
-    DoesNotExist { uri: String },
+    AlreadyExists {
+        uri: String,
+    },
+    /// The note was changed by someone else since it was loaded.
+    Conflict {
+        uri: String,
+    },
+    DecodeError(String),
+    DoesNotExist {
+        uri: String,
+    },
+    /// The text contains characters the note's encoding can't represent.
+    EncodeError(String),
     IoError(glib::Error),
+    NotEmpty {
+        uri: String,
+    },
     OtherError(String),
+    /// Wraps an error with the storage operation that failed.
+    StorageError {
+        operation: Operation,
+        location: String,
+        backend: &'static str,
+        source: Box<Error>,
+    },
+    TrashNotSupported {
+        uri: String,
+    },
     UnknownStorageBackend(String),
 }
 
+impl Error {
+    pub fn context(self, operation: Operation, location: String, backend: &'static str) -> Error {
+        Error::StorageError {
+            operation,
+            location,
+            backend,
+            source: Box::new(self),
+        }
+    }
+
+    /// The underlying error, without any operation context.
+    pub fn cause(&self) -> &Error {
+        match self {
+            Error::StorageError { source, .. } => source.cause(),
+            err => err,
+        }
+    }
+
+    pub fn operation(&self) -> Option<Operation> {
+        match self {
+            Error::StorageError { operation, .. } => Some(*operation),
+            _ => None,
+        }
+    }
+
+    /// A short, single-line description suitable for a toast.
+    pub fn summary(&self) -> String {
+        match self {
+            Error::StorageError {
+                operation,
+                location,
+                source,
+                ..
+            } => {
+                let name = location
+                    .trim_end_matches('/')
+                    .rsplit('/')
+                    .next()
+                    .unwrap_or(location);
+                let name = glib::Uri::unescape_string(name, None::<&str>)
+                    .map_or(name.to_string(), |name| name.to_string());
+
+                format!("Could not {} “{}”: {}", operation, name, source.cause())
+            }
+            err => err.to_string(),
+        }
+    }
+}
+
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            Error::DecodeError(err) => write!(f, "{}", err.to_string()),
+            Error::AlreadyExists { uri } => write!(f, "{} already exists", uri),
+            Error::Conflict { uri } => write!(f, "{} was changed elsewhere", uri),
+            Error::DecodeError(err) => write!(f, "{}", err),
             Error::DoesNotExist { uri } => write!(f, "could not find {}", uri),
+            Error::EncodeError(err) => write!(f, "{}", err),
             Error::IoError(err) => write!(f, "{}", err.to_string()),
+            Error::NotEmpty { uri } => write!(f, "{} is not empty", uri),
             Error::OtherError(msg) => write!(f, "{}", msg),
+            Error::StorageError {
+                operation,
+                location,
+                backend,
+                source,
+            } => write!(
+                f,
+                "could not {} {} ({} storage): {}",
+                operation, location, backend, source
+            ),
+            Error::TrashNotSupported { uri } => write!(f, "cannot move {} to the trash", uri),
             Error::UnknownStorageBackend(err) => write!(f, "{}", err.to_string()),
         }
     }
@@ -31,7 +165,7 @@ impl From<glib::Error> for Error {
 
 impl From<FromUtf8Error> for Error {
     fn from(err: FromUtf8Error) -> Error {
-        Error::DecodeError(err)
+        Error::DecodeError(err.to_string())
     }
 }
 
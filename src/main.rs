@@ -1,10 +1,10 @@
 mod errors;
 mod persistence;
+mod settings;
 mod ui;
 mod util;
 
-use std::{env, path::PathBuf};
-
+use crate::settings::Settings;
 use crate::ui::app::App;
 use relm4::RelmApp;
 
@@ -16,18 +16,15 @@ const APP_ID: &str = "de.fqxp.notes";
 const APP_NAME: &str = "notes";
 const GITHUB_URL: &str = "https://github.com/fqxp/notes-rust";
 const VERSION: &str = "0.1";
+/// Domain of the messages the app writes to GLib's log.
+const LOG_DOMAIN: &str = APP_NAME;
 
 fn main() -> Result<(), ()> {
     relm4_icons::initialize_icons(icon_names::GRESOURCE_BYTES, icon_names::RESOURCE_PREFIX);
 
-    let notes_rootdir =
-        PathBuf::from(env::var_os("NOTES_ROOTDIR").unwrap_or("./sample-notes".into()))
-            .canonicalize()
-            .unwrap();
-    let uri = format!("file://{}", notes_rootdir.to_string_lossy());
     let app = RelmApp::new(APP_ID);
 
-    app.run_async::<App>(uri);
+    app.run_async::<App>(Settings::from_env());
 
     Ok(())
 }
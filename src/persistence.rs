@@ -1,15 +1,27 @@
+pub mod file_rules;
 pub mod filesystem;
+pub mod frontmatter;
+pub mod index;
+pub mod links;
 pub mod models;
 pub mod storage;
+pub mod tags;
+pub mod text_format;
 
-use filesystem::FilesystemStorage;
-use storage::{DynItemStorage, ItemStorage};
+use file_rules::FileRules;
+use filesystem::{Filesystem, FilesystemStorage};
+use storage::{DynItemStorage, ItemStorage, StorageBackend};
 
-use crate::errors::Error;
+use crate::errors::{Error, Operation};
 
-pub async fn build_storage_from_url(uri: &str) -> Result<Box<dyn ItemStorage>, Error> {
+pub async fn build_storage_from_url(
+    uri: &str,
+    file_rules: &FileRules,
+) -> Result<Box<dyn ItemStorage>, Error> {
     if uri.starts_with("file://") {
-        let fs_storage = FilesystemStorage::from_uri(uri).await?;
+        let fs_storage = FilesystemStorage::from_uri(uri, file_rules.clone())
+            .await
+            .map_err(|err| err.context(Operation::OpenStorage, uri.to_owned(), Filesystem::NAME))?;
         Ok(Box::new(DynItemStorage {
             inner: Box::new(fs_storage),
         }))
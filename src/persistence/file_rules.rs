@@ -0,0 +1,141 @@
+/// Extensions of files shown as notes, whatever their content type.
+pub const DEFAULT_NOTE_EXTENSIONS: &[&str] = &[
+    "md", "markdown", "mdown", "txt", "text", "org", "adoc", "asciidoc", "rst",
+];
+
+/// Files left out of notebooks: hidden files, which covers `.git` and
+/// `.stfolder`, plus the backup and swap files editors leave behind.
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &[".*", "*~", "*.swp", "*.swo", "#*#", "*.tmp"];
+
+/// Tells which files of a notebook are notes, which are attachments and
+/// which aren't shown at all.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileRules {
+    /// extensions of note files, without the dot
+    pub note_extensions: Vec<String>,
+    /// name patterns of ignored files and folders, where `*` matches any
+    /// number of characters and `?` a single one
+    pub ignore_patterns: Vec<String>,
+}
+
+impl Default for FileRules {
+    fn default() -> Self {
+        Self {
+            note_extensions: DEFAULT_NOTE_EXTENSIONS
+                .iter()
+                .map(|extension| extension.to_string())
+                .collect(),
+            ignore_patterns: DEFAULT_IGNORE_PATTERNS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl FileRules {
+    pub fn is_ignored(&self, name: &str) -> bool {
+        self.ignore_patterns
+            .iter()
+            .any(|pattern| matches_pattern(pattern, name))
+    }
+
+    /// Whether a file is a note rather than an attachment. Files with a note
+    /// extension always are, others only if they contain plain text, like a
+    /// `README` without extension.
+    pub fn is_note(&self, name: &str, mime_type: &str) -> bool {
+        self.has_note_extension(name) || mime_type == "text/plain"
+    }
+
+    /// Whether `name` ends in one of the note extensions, unlike e.g.
+    /// `v1.2 notes`.
+    pub fn has_note_extension(&self, name: &str) -> bool {
+        name.rsplit_once('.')
+            .filter(|(stem, _)| !stem.is_empty())
+            .is_some_and(|(_, extension)| {
+                self.note_extensions
+                    .iter()
+                    .any(|known| known.eq_ignore_ascii_case(extension))
+            })
+    }
+}
+
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // where to continue after the last `*` if the rest doesn't match
+    let mut backtrack: Option<(usize, usize)> = None;
+    let (mut p, mut n) = (0, 0);
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    backtrack = Some((star, matched + 1));
+                    p = star + 1;
+                    n = matched + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ignored() {
+        let rules = FileRules::default();
+
+        assert!(rules.is_ignored(".git"));
+        assert!(rules.is_ignored(".stfolder"));
+        assert!(rules.is_ignored(".note.md.swp"));
+        assert!(rules.is_ignored("note.md~"));
+        assert!(rules.is_ignored("#note.md#"));
+        assert!(!rules.is_ignored("note.md"));
+        assert!(!rules.is_ignored("swp"));
+    }
+
+    #[test]
+    fn test_matches_pattern() {
+        assert!(matches_pattern("*.sw?", "note.swp"));
+        assert!(matches_pattern("a*b*c", "aXbYbc"));
+        assert!(!matches_pattern("a*b*c", "aXbYbd"));
+        assert!(matches_pattern("*", ""));
+    }
+
+    #[test]
+    fn test_is_note() {
+        let rules = FileRules::default();
+
+        assert!(rules.is_note("Plan.MD", "application/octet-stream"));
+        assert!(rules.is_note("README", "text/plain"));
+        assert!(rules.is_note("notes.log", "text/plain"));
+        assert!(!rules.is_note("photo.png", "image/png"));
+        assert!(!rules.is_note("drawing.svg", "image/svg+xml"));
+        assert!(!rules.is_note("paper.pdf", "application/pdf"));
+    }
+
+    #[test]
+    fn test_has_note_extension() {
+        let rules = FileRules::default();
+
+        assert!(rules.has_note_extension("Plan.md"));
+        assert!(rules.has_note_extension("notes.TXT"));
+        assert!(!rules.has_note_extension("v1.2 notes"));
+        assert!(!rules.has_note_extension("Dr. Who"));
+        assert!(!rules.has_note_extension(".md"));
+    }
+}
@@ -1,16 +1,22 @@
+use std::future::Future;
 use std::path::PathBuf;
+use std::pin::Pin;
+use std::rc::Rc;
 
 use async_trait::async_trait;
 use gtk::gio::prelude::*;
 use gtk::{gio, glib};
 
+use crate::LOG_DOMAIN;
 use crate::errors::Error;
 
-use super::models::{AnyNote, CollectionPath, Meta};
+use super::file_rules::FileRules;
+use super::models::{AnyAttachment, AnyCollection, AnyNote, CollectionPath, ItemKind, Meta};
 use super::storage::StorageBackend;
+use super::text_format::TextFormat;
 use super::{
-    models::{AnyItem, Attachment, Collection, Note},
-    storage::{NoteContent, TypedItemStorage},
+    models::{AnyItem, Attachment, Collection, Note, TypedItem},
+    storage::{AttachmentInfo, ItemEvent, NoteContent, SearchMatch, TypedItemStorage, Watch},
 };
 
 #[derive(Debug, Clone)]
@@ -22,17 +28,22 @@ impl Meta for FilesystemMeta {}
 pub struct Filesystem;
 
 impl StorageBackend for Filesystem {
+    const NAME: &'static str = "filesystem";
+
     type NoteMeta = FilesystemMeta;
     type CollectionMeta = FilesystemMeta;
     type AttachmentMeta = FilesystemMeta;
 }
 
+#[derive(Clone)]
 pub struct FilesystemStorage {
     pub root: Collection<Filesystem>,
+    /// which files are notes and which are left out
+    rules: FileRules,
 }
 
 impl FilesystemStorage {
-    pub async fn from_uri(root_uri: &str) -> Result<Self, Error> {
+    pub async fn from_uri(root_uri: &str, rules: FileRules) -> Result<Self, Error> {
         let file = gio::File::for_uri(root_uri);
         if !file.query_exists(gio::Cancellable::NONE) {
             return Err(Error::DoesNotExist {
@@ -59,7 +70,7 @@ impl FilesystemStorage {
             root_uri.to_string(),
         );
 
-        Ok(Self { root })
+        Ok(Self { root, rules })
     }
 
     fn path_from_uri(&self, uri: String) -> Option<PathBuf> {
@@ -69,6 +80,310 @@ impl FilesystemStorage {
 
         Some(PathBuf::from(uri[4..].to_string()))
     }
+
+    fn item_from_file_info(&self, file: &gio::File, file_info: &gio::FileInfo) -> Box<dyn AnyItem> {
+        match file_info.file_type() {
+            gio::FileType::Regular if self.is_note(file_info) => Box::new(
+                Note::<Filesystem>::new(
+                    FilesystemMeta {},
+                    file_info.name().to_string_lossy().to_string(),
+                    file_info
+                        .modification_date_time()
+                        .expect("modification time should be set"),
+                    file.uri().to_string(),
+                )
+                .with_etag(file_info.etag().map(|etag| etag.to_string())),
+            ) as Box<dyn AnyItem>,
+            gio::FileType::Directory => Box::new(Collection::<Filesystem>::new(
+                FilesystemMeta {},
+                file_info.name().to_string_lossy().to_string(),
+                file_info
+                    .modification_date_time()
+                    .expect("modification time should be set"),
+                file.uri().to_string(),
+            )) as Box<dyn AnyItem>,
+            _ => Box::new(Attachment::<Filesystem>::new(
+                FilesystemMeta {},
+                file_info.name().to_string_lossy().to_string(),
+                file_info
+                    .modification_date_time()
+                    .expect("modification time should be set"),
+                file.uri().to_string(),
+            )) as Box<dyn AnyItem>,
+        }
+    }
+
+    fn is_note(&self, file_info: &gio::FileInfo) -> bool {
+        let mime_type = file_info
+            .content_type()
+            .and_then(|content_type| gio::content_type_get_mime_type(&content_type))
+            .unwrap_or_default();
+
+        self.rules
+            .is_note(&file_info.name().to_string_lossy(), &mime_type)
+    }
+
+    async fn item_from_file(&self, file: &gio::File) -> Result<Box<dyn AnyItem>, Error> {
+        let file_info = file
+            .query_info_future(
+                "standard::*,time::*,etag::value",
+                gio::FileQueryInfoFlags::NONE,
+                glib::Priority::DEFAULT,
+            )
+            .await?;
+
+        Ok(self.item_from_file_info(file, &file_info))
+    }
+
+    /// Collects the notes in the last collection of `path` and all
+    /// collections below it. Collections below it that can't be read are
+    /// skipped, and symbolic links to folders aren't followed, as they might
+    /// lead back up.
+    fn collect_notes<'a>(
+        &'a self,
+        path: CollectionPath,
+        notes: &'a mut Vec<(Box<dyn AnyNote>, CollectionPath)>,
+    ) -> Pin<Box<dyn Future<Output = Result<(), Error>> + 'a>> {
+        Box::pin(async move {
+            for item in self.list_items(&path).await? {
+                match item.kind() {
+                    ItemKind::Collection => {
+                        let file = gio::File::for_uri(&item.location());
+                        let is_symlink = file
+                            .query_info_future(
+                                "standard::is-symlink",
+                                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                                glib::Priority::DEFAULT,
+                            )
+                            .await
+                            .is_ok_and(|file_info| file_info.is_symlink());
+                        if is_symlink {
+                            continue;
+                        }
+
+                        let mut sub_path = path.clone();
+                        sub_path.push(item.as_collection().expect("collection"));
+                        if let Err(err) = self.collect_notes(sub_path, notes).await {
+                            glib::g_warning!(
+                                LOG_DOMAIN,
+                                "skipped {} that can't be read: {}",
+                                item.location(),
+                                err
+                            );
+                        }
+                    }
+                    ItemKind::Note => notes.push((item.as_note().expect("note"), path.clone())),
+                    ItemKind::Attachment => {}
+                }
+            }
+
+            Ok(())
+        })
+    }
+
+    /// Forwards the changes reported by `monitor` to `on_event`.
+    fn watch(&self, monitor: gio::FileMonitor, on_event: Box<dyn Fn(ItemEvent)>) -> Watch {
+        let storage = self.clone();
+        let on_event = Rc::new(on_event);
+        monitor.connect_changed(move |_, file, other_file, event_type| {
+            let storage = storage.clone();
+            let on_event = on_event.clone();
+            let file = file.clone();
+            let other_file = other_file.cloned();
+            glib::spawn_future_local(async move {
+                if let Some(event) = storage.item_event(file, other_file, event_type).await {
+                    on_event(event);
+                }
+            });
+        });
+
+        Watch::new(move || {
+            monitor.cancel();
+        })
+    }
+
+    async fn item_event(
+        &self,
+        file: gio::File,
+        other_file: Option<gio::File>,
+        event_type: gio::FileMonitorEvent,
+    ) -> Option<ItemEvent> {
+        // editors' swap files and the like come and go all the time
+        let is_ignored = |file: &gio::File| {
+            file.basename()
+                .is_some_and(|name| self.rules.is_ignored(&name.to_string_lossy()))
+        };
+        if is_ignored(&file) && other_file.as_ref().is_none_or(is_ignored) {
+            return None;
+        }
+
+        // items may be gone again by the time they are looked at, e.g. temporary
+        // files written by editors, so failing lookups just drop the event
+        match event_type {
+            gio::FileMonitorEvent::Created | gio::FileMonitorEvent::MovedIn => {
+                Some(ItemEvent::Created(self.item_from_file(&file).await.ok()?))
+            }
+            gio::FileMonitorEvent::ChangesDoneHint => {
+                Some(ItemEvent::Changed(self.item_from_file(&file).await.ok()?))
+            }
+            gio::FileMonitorEvent::Deleted | gio::FileMonitorEvent::MovedOut => {
+                Some(ItemEvent::Deleted {
+                    location: file.uri().to_string(),
+                })
+            }
+            gio::FileMonitorEvent::Renamed => Some(ItemEvent::Moved {
+                location: file.uri().to_string(),
+                item: self.item_from_file(&other_file?).await.ok()?,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Resolves where `item` ends up when moved or copied into `destination`,
+    /// refusing to put a collection inside itself.
+    fn transfer_target(
+        &self,
+        item: &dyn AnyItem,
+        destination: &Collection<Filesystem>,
+    ) -> Result<(gio::File, gio::File), Error> {
+        let file = gio::File::for_uri(&item.location());
+        let dest_dir = gio::File::for_uri(&destination.location());
+
+        if dest_dir.equal(&file) || dest_dir.has_prefix(&file) {
+            return Err(Error::OtherError(format!(
+                "cannot put {} into itself",
+                item.name()
+            )));
+        }
+
+        let dest_file = dest_dir.child(file.basename().expect("valid file name"));
+
+        Ok((file, dest_file))
+    }
+
+    async fn collection_from_file(
+        &self,
+        file: &gio::File,
+    ) -> Result<Box<dyn AnyCollection>, Error> {
+        let file_info = file
+            .query_info_future(
+                "standard::*,time::*",
+                gio::FileQueryInfoFlags::NONE,
+                glib::Priority::DEFAULT,
+            )
+            .await?;
+
+        Ok(Box::new(Collection::<Filesystem>::new(
+            FilesystemMeta {},
+            file_info.name().to_string_lossy().to_string(),
+            file_info
+                .modification_date_time()
+                .expect("valid modification time"),
+            file.uri().to_string(),
+        )))
+    }
+}
+
+// gio has no recursive delete, so walk the tree depth-first
+fn delete_recursively(file: gio::File) -> Pin<Box<dyn Future<Output = Result<(), Error>>>> {
+    Box::pin(async move {
+        let file_type = file
+            .query_info_future(
+                "standard::type",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                glib::Priority::DEFAULT,
+            )
+            .await?
+            .file_type();
+
+        if file_type == gio::FileType::Directory {
+            let file_infos = file
+                .enumerate_children_future(
+                    "standard::name",
+                    gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                    glib::Priority::DEFAULT,
+                )
+                .await?;
+
+            for file_info in file_infos {
+                delete_recursively(file.child(file_info?.name())).await?;
+            }
+        }
+
+        file.delete_future(glib::Priority::DEFAULT).await?;
+
+        Ok(())
+    })
+}
+
+// gio refuses to copy directories, so recreate the tree file by file
+fn copy_recursively(
+    src: gio::File,
+    dest: gio::File,
+) -> Pin<Box<dyn Future<Output = Result<(), Error>>>> {
+    Box::pin(async move {
+        let file_type = src
+            .query_info_future(
+                "standard::type",
+                gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                glib::Priority::DEFAULT,
+            )
+            .await?
+            .file_type();
+
+        if file_type == gio::FileType::Directory {
+            dest.make_directory_future(glib::Priority::DEFAULT)
+                .await
+                .map_err(|err| map_exists_error(err, &dest))?;
+
+            let file_infos = src
+                .enumerate_children_future(
+                    "standard::name",
+                    gio::FileQueryInfoFlags::NOFOLLOW_SYMLINKS,
+                    glib::Priority::DEFAULT,
+                )
+                .await?;
+
+            for file_info in file_infos {
+                let name = file_info?.name();
+                copy_recursively(src.child(&name), dest.child(&name)).await?;
+            }
+        } else {
+            let (result, _) = src.copy_future(
+                &dest,
+                gio::FileCopyFlags::NOFOLLOW_SYMLINKS | gio::FileCopyFlags::ALL_METADATA,
+                glib::Priority::DEFAULT,
+            );
+            result.await.map_err(|err| map_exists_error(err, &dest))?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Names the `i`th copy of an item, keeping a note's or attachment's file
+/// extension at the end.
+fn copy_name(name: &str, kind: ItemKind, i: usize) -> String {
+    let (stem, extension) = match name.rfind('.') {
+        Some(dot) if dot > 0 && kind != ItemKind::Collection => name.split_at(dot),
+        _ => (name, ""),
+    };
+
+    if i == 1 {
+        format!("{} (copy){}", stem, extension)
+    } else {
+        format!("{} (copy {}){}", stem, i, extension)
+    }
+}
+
+fn map_exists_error(err: glib::Error, file: &gio::File) -> Error {
+    if err.matches(gio::IOErrorEnum::Exists) {
+        Error::AlreadyExists {
+            uri: file.uri().to_string(),
+        }
+    } else {
+        Error::IoError(err)
+    }
 }
 
 #[async_trait(?Send)]
@@ -87,49 +402,65 @@ impl TypedItemStorage<Filesystem> for FilesystemStorage {
 
         let file_infos = dir
             .enumerate_children_future(
-                "standard::*,time::*",
+                "standard::*,time::*,etag::value",
                 gio::FileQueryInfoFlags::NONE,
                 glib::Priority::DEFAULT,
             )
             .await?;
 
-        let result: Vec<Box<dyn AnyItem>> = file_infos
+        let result = file_infos
+            .filter(|file_info| {
+                file_info.as_ref().map_or(true, |file_info| {
+                    !self.rules.is_ignored(&file_info.name().to_string_lossy())
+                })
+            })
             .map(|file_info| {
-                let file_info = file_info.unwrap();
+                let file_info = file_info?;
                 let file = dir.child(file_info.name());
 
-                match file_info.file_type() {
-                    gio::FileType::Regular => Box::new(Note::<Filesystem>::new(
-                        FilesystemMeta {},
-                        file_info.name().to_string_lossy().to_string(),
-                        file_info
-                            .modification_date_time()
-                            .expect("modification time should be set"),
-                        file.uri().to_string(),
-                    )) as Box<dyn AnyItem>,
-                    gio::FileType::Directory => Box::new(Collection::<Filesystem>::new(
-                        FilesystemMeta {},
-                        file_info.name().to_string_lossy().to_string(),
-                        file_info
-                            .modification_date_time()
-                            .expect("modification time should be set"),
-                        file.uri().to_string(),
-                    )) as Box<dyn AnyItem>,
-                    _ => Box::new(Attachment::<Filesystem>::new(
-                        FilesystemMeta {},
-                        file_info.name().to_string_lossy().to_string(),
-                        file_info
-                            .modification_date_time()
-                            .expect("modification time should be set"),
-                        file.uri().to_string(),
-                    )) as Box<dyn AnyItem>,
-                }
+                Ok(self.item_from_file_info(&file, &file_info))
             })
-            .collect();
+            .collect::<Result<Vec<Box<dyn AnyItem>>, Error>>()?;
 
         Result::Ok(result)
     }
 
+    async fn create_note(
+        &self,
+        collection: &Collection<Filesystem>,
+        name: &str,
+        initial_content: &str,
+    ) -> Result<Box<dyn AnyNote>, Error> {
+        let file = gio::File::for_uri(&collection.location()).child(name);
+
+        // create() fails instead of truncating when the file already exists
+        let stream = file
+            .create_future(gio::FileCreateFlags::NONE, glib::Priority::DEFAULT)
+            .await
+            .map_err(|err| map_exists_error(err, &file))?;
+        stream
+            .write_all_future(initial_content.as_bytes().to_vec(), glib::Priority::DEFAULT)
+            .await?;
+        stream.close_future(glib::Priority::DEFAULT).await?;
+
+        let file_info = file
+            .query_info_future(
+                "time::*",
+                gio::FileQueryInfoFlags::NONE,
+                glib::Priority::DEFAULT,
+            )
+            .await?;
+
+        Ok(Box::new(Note::<Filesystem>::new(
+            FilesystemMeta {},
+            String::from(name),
+            file_info
+                .modification_date_time()
+                .expect("valid modification time"),
+            file.uri().to_string(),
+        )))
+    }
+
     async fn rename_note(
         &self,
         note: &Note<Filesystem>,
@@ -159,21 +490,242 @@ impl TypedItemStorage<Filesystem> for FilesystemStorage {
                 file_info
                     .modification_date_time()
                     .expect("valid modification time"),
-                format!("file://{:?}", dest_file.path().expect("valid path")),
+                dest_file.uri().to_string(),
             )))
         } else {
             Err(Error::OtherError("error moving file".to_string()))
         }
     }
 
+    async fn create_collection(
+        &self,
+        parent: &Collection<Filesystem>,
+        name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error> {
+        let dir = gio::File::for_uri(&parent.location()).child(name);
+        dir.make_directory_future(glib::Priority::DEFAULT)
+            .await
+            .map_err(|err| map_exists_error(err, &dir))?;
+
+        self.collection_from_file(&dir).await
+    }
+
+    async fn rename_collection(
+        &self,
+        collection: &Collection<Filesystem>,
+        new_name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error> {
+        let dir = gio::File::for_uri(&collection.location());
+        let renamed_dir = dir
+            .set_display_name_future(new_name, glib::Priority::DEFAULT)
+            .await
+            .map_err(|err| match dir.parent() {
+                Some(parent) => map_exists_error(err, &parent.child(new_name)),
+                None => Error::IoError(err),
+            })?;
+
+        self.collection_from_file(&renamed_dir).await
+    }
+
+    async fn delete_collection(
+        &self,
+        collection: &Collection<Filesystem>,
+        recursive: bool,
+    ) -> Result<(), Error> {
+        let dir = gio::File::for_uri(&collection.location());
+
+        if recursive {
+            delete_recursively(dir).await
+        } else {
+            dir.delete_future(glib::Priority::DEFAULT)
+                .await
+                .map_err(|err| {
+                    if err.matches(gio::IOErrorEnum::NotEmpty) {
+                        Error::NotEmpty {
+                            uri: dir.uri().to_string(),
+                        }
+                    } else {
+                        Error::IoError(err)
+                    }
+                })
+        }
+    }
+
+    async fn delete_item(
+        &self,
+        item: TypedItem<'_, Filesystem>,
+        permanently: bool,
+    ) -> Result<(), Error> {
+        let file = gio::File::for_uri(&item.location());
+
+        if permanently {
+            return delete_recursively(file).await;
+        }
+
+        file.trash_future(glib::Priority::DEFAULT)
+            .await
+            .map_err(|err| {
+                if err.matches(gio::IOErrorEnum::NotSupported) {
+                    Error::TrashNotSupported {
+                        uri: file.uri().to_string(),
+                    }
+                } else {
+                    Error::IoError(err)
+                }
+            })
+    }
+
+    async fn restore_item(&self, item: TypedItem<'_, Filesystem>) -> Result<(), Error> {
+        let file = gio::File::for_uri(&item.location());
+        let path = file.path().ok_or_else(|| Error::DoesNotExist {
+            uri: item.location(),
+        })?;
+
+        // the trash only remembers where an item came from, so look for the
+        // most recently deleted entry with a matching original path
+        let trash = gio::File::for_uri("trash:///");
+        let file_infos = trash
+            .enumerate_children_future(
+                "standard::name,trash::orig-path,trash::deletion-date",
+                gio::FileQueryInfoFlags::NONE,
+                glib::Priority::DEFAULT,
+            )
+            .await?;
+
+        let mut trashed: Option<gio::FileInfo> = None;
+        for file_info in file_infos {
+            let file_info = file_info?;
+            let orig_path = file_info
+                .attribute_byte_string("trash::orig-path")
+                .map(|orig_path| PathBuf::from(orig_path.as_str()));
+            if orig_path.as_ref() != Some(&path) {
+                continue;
+            }
+            if trashed
+                .as_ref()
+                .is_none_or(|t| t.deletion_date() < file_info.deletion_date())
+            {
+                trashed = Some(file_info);
+            }
+        }
+
+        let Some(trashed) = trashed else {
+            return Err(Error::DoesNotExist {
+                uri: item.location(),
+            });
+        };
+        let (result, _) = trash.child(trashed.name()).move_future(
+            &file,
+            gio::FileCopyFlags::NOFOLLOW_SYMLINKS,
+            glib::Priority::DEFAULT,
+        );
+        result.await.map_err(|err| map_exists_error(err, &file))
+    }
+
+    async fn move_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &Collection<Filesystem>,
+    ) -> Result<Box<dyn AnyItem>, Error> {
+        let (file, dest_file) = self.transfer_target(item, destination)?;
+        if dest_file.equal(&file) {
+            return Ok(item.clone_box());
+        }
+
+        let (result, _) = file.move_future(
+            &dest_file,
+            gio::FileCopyFlags::NOFOLLOW_SYMLINKS | gio::FileCopyFlags::ALL_METADATA,
+            glib::Priority::DEFAULT,
+        );
+        match result.await {
+            Ok(()) => {}
+            // directories can only be renamed, not moved across filesystems
+            Err(err) if err.matches(gio::IOErrorEnum::WouldRecurse) => {
+                copy_recursively(file.clone(), dest_file.clone()).await?;
+                delete_recursively(file).await?;
+            }
+            Err(err) => return Err(map_exists_error(err, &dest_file)),
+        }
+
+        self.item_from_file(&dest_file).await
+    }
+
+    async fn copy_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &Collection<Filesystem>,
+    ) -> Result<Box<dyn AnyItem>, Error> {
+        let (file, dest_file) = self.transfer_target(item, destination)?;
+
+        let dest_dir = gio::File::for_uri(&destination.location());
+        if !file.parent().is_some_and(|parent| parent.equal(&dest_dir)) {
+            copy_recursively(file, dest_file.clone()).await?;
+            return self.item_from_file(&dest_file).await;
+        }
+
+        // a copy next to the original is named after it, picking the first
+        // "Plan (copy).md", "Plan (copy 2).md", … that is not taken yet
+        let name = item.name();
+        for i in 1.. {
+            let dest_file = dest_dir.child(copy_name(&name, item.kind(), i));
+            match copy_recursively(file.clone(), dest_file.clone()).await {
+                Err(Error::AlreadyExists { .. }) => continue,
+                Err(err) => return Err(err),
+                Ok(()) => return self.item_from_file(&dest_file).await,
+            }
+        }
+
+        unreachable!()
+    }
+
     async fn load_content(&self, note: &Note<Filesystem>) -> Result<NoteContent, Error> {
         let file = gio::File::for_uri(&note.location());
         let (content, etag) = file.load_contents_future().await?;
-        let content = String::from_utf8(content.to_vec())?;
+        let (content, format) = TextFormat::decode(&content)?;
         let etag = etag.and_then(|g_string| Some(g_string.to_string()));
-        println!("load_content etag={:?}", &etag);
 
-        return Ok(NoteContent { content, etag });
+        return Ok(NoteContent {
+            content,
+            etag,
+            format,
+        });
+    }
+
+    async fn load_bytes(&self, item: &dyn AnyItem) -> Result<Vec<u8>, Error> {
+        let file = gio::File::for_uri(&item.location());
+        let (content, _) = file.load_contents_future().await?;
+
+        Ok(content.to_vec())
+    }
+
+    async fn read_stream(&self, item: &dyn AnyItem) -> Result<gio::InputStream, Error> {
+        let file = gio::File::for_uri(&item.location());
+        let stream = file.read_future(glib::Priority::DEFAULT).await?;
+
+        Ok(stream.upcast())
+    }
+
+    async fn attachment_info(
+        &self,
+        attachment: &dyn AnyAttachment,
+    ) -> Result<AttachmentInfo, Error> {
+        let file = gio::File::for_uri(&attachment.location());
+        let file_info = file
+            .query_info_future(
+                "standard::size,standard::content-type",
+                gio::FileQueryInfoFlags::NONE,
+                glib::Priority::DEFAULT,
+            )
+            .await?;
+
+        Ok(AttachmentInfo {
+            size: file_info.size().max(0) as u64,
+            content_type: file_info
+                .content_type()
+                .map_or("application/octet-stream".to_string(), |content_type| {
+                    content_type.to_string()
+                }),
+        })
     }
 
     async fn save_content(
@@ -184,14 +736,70 @@ impl TypedItemStorage<Filesystem> for FilesystemStorage {
         let file = gio::File::for_uri(&note.location());
         let (_, etag_after_save) = file
             .replace_contents_future(
-                content.content.as_bytes().to_vec(),
+                content.format.encode(&content.content)?,
                 content.etag.as_deref(),
                 false,
                 gio::FileCreateFlags::NONE,
             )
-            .await?;
-        println!("save_content etag={:?}", &content.etag);
+            .await
+            .map_err(|(_, err)| {
+                if err.matches(gio::IOErrorEnum::WrongEtag) {
+                    Error::Conflict {
+                        uri: file.uri().to_string(),
+                    }
+                } else {
+                    Error::IoError(err)
+                }
+            })?;
 
         Result::Ok(etag_after_save.to_string())
     }
+
+    async fn list_notes(
+        &self,
+        path: &CollectionPath,
+    ) -> Result<Vec<(Box<dyn AnyNote>, CollectionPath)>, Error> {
+        let mut notes = Vec::new();
+        self.collect_notes(path.clone(), &mut notes).await?;
+
+        Ok(notes)
+    }
+
+    async fn search(&self, query: &str, scope: &CollectionPath) -> Result<Vec<SearchMatch>, Error> {
+        let mut matches = Vec::new();
+        for (note, path) in self.list_notes(scope).await? {
+            // notes that can't be read as text just don't match
+            let Ok(content) = self
+                .load_content(Note::<Filesystem>::from_any(note.as_ref()).unwrap())
+                .await
+            else {
+                continue;
+            };
+            matches.extend(SearchMatch::find(note, &path, &content.content, query));
+        }
+
+        Ok(matches)
+    }
+
+    fn watch_collection(
+        &self,
+        collection: &Collection<Filesystem>,
+        on_event: Box<dyn Fn(ItemEvent)>,
+    ) -> Result<Watch, Error> {
+        let monitor = gio::File::for_uri(&collection.location())
+            .monitor_directory(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)?;
+
+        Ok(self.watch(monitor, on_event))
+    }
+
+    fn watch_note(
+        &self,
+        note: &Note<Filesystem>,
+        on_event: Box<dyn Fn(ItemEvent)>,
+    ) -> Result<Watch, Error> {
+        let monitor = gio::File::for_uri(&note.location())
+            .monitor_file(gio::FileMonitorFlags::WATCH_MOVES, gio::Cancellable::NONE)?;
+
+        Ok(self.watch(monitor, on_event))
+    }
 }
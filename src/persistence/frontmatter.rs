@@ -0,0 +1,711 @@
+use std::ops::Range;
+
+/// How the frontmatter of a note is written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// YAML between `---` lines
+    Yaml,
+    /// TOML between `+++` lines
+    Toml,
+}
+
+impl Format {
+    fn delimiter(&self) -> &'static str {
+        match self {
+            Format::Yaml => "---",
+            Format::Toml => "+++",
+        }
+    }
+}
+
+/// A frontmatter value. Anything that isn't a list is kept as text.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Text(String),
+    List(Vec<String>),
+}
+
+impl Value {
+    /// Items of a list, or the comma separated parts of a text.
+    pub fn items(&self) -> Vec<String> {
+        match self {
+            Value::Text(text) => text
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(String::from)
+                .collect(),
+            Value::List(items) => items.clone(),
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        match self {
+            Value::Text(text) => text.clone(),
+            Value::List(items) => items.join(", "),
+        }
+    }
+}
+
+/// Metadata of a note, as given in its frontmatter.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    pub title: Option<String>,
+    pub tags: Vec<String>,
+    pub aliases: Vec<String>,
+    pub created: Option<String>,
+    /// all other top-level fields, in the order they appear
+    pub custom: Vec<(String, Value)>,
+}
+
+impl Metadata {
+    /// The metadata in the frontmatter of `content`, empty if there is none.
+    pub fn parse(content: &str) -> Metadata {
+        Frontmatter::parse(content)
+            .map(|frontmatter| frontmatter.metadata)
+            .unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.title.is_none()
+            && self.tags.is_empty()
+            && self.aliases.is_empty()
+            && self.created.is_none()
+            && self.custom.is_empty()
+    }
+
+    fn add(&mut self, key: &str, value: Value) {
+        match key.to_lowercase().as_str() {
+            "title" => self.title = Some(value.to_text()),
+            "tags" => {
+                self.tags = value
+                    .items()
+                    .into_iter()
+                    .map(|tag| tag.trim_start_matches('#').to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect()
+            }
+            "aliases" => self.aliases = value.items(),
+            "created" | "date" => self.created = Some(value.to_text()),
+            _ => self.custom.push((key.to_string(), value)),
+        }
+    }
+}
+
+/// A top-level field and where it is written.
+#[derive(Debug)]
+struct Field {
+    key: String,
+    /// lines of the field, including the last line break
+    range: Range<usize>,
+    /// indentation of the items of a YAML list written one item per line
+    list_indent: Option<String>,
+}
+
+/// Frontmatter at the start of a note.
+#[derive(Debug)]
+pub struct Frontmatter {
+    pub format: Format,
+    pub metadata: Metadata,
+    fields: Vec<Field>,
+    /// where new top-level fields go: before the closing delimiter, or
+    /// before the first table in TOML
+    end: usize,
+    /// where the note body starts
+    body_start: usize,
+    newline: &'static str,
+}
+
+/// A line with its position in the content.
+struct Line<'a> {
+    start: usize,
+    /// after the line break
+    end: usize,
+    text: &'a str,
+}
+
+fn lines(content: &str) -> impl Iterator<Item = Line<'_>> {
+    let mut offset = 0;
+
+    content.split_inclusive('\n').map(move |line| {
+        let start = offset;
+        offset += line.len();
+
+        Line {
+            start,
+            end: offset,
+            text: line.trim_end_matches('\n').trim_end_matches('\r'),
+        }
+    })
+}
+
+impl Frontmatter {
+    /// Parses the frontmatter at the start of `content`, if there is any.
+    pub fn parse(content: &str) -> Option<Frontmatter> {
+        let mut lines = lines(content);
+        let first = lines.next()?;
+        let format = match first.text.trim_end() {
+            "---" => Format::Yaml,
+            "+++" => Format::Toml,
+            _ => return None,
+        };
+        let newline = if content[..first.end].ends_with("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        };
+
+        let mut entries = Vec::new();
+        let mut closing = None;
+        for line in lines {
+            let text = line.text.trim_end();
+            if text == format.delimiter() || (format == Format::Yaml && text == "...") {
+                closing = Some(line);
+                break;
+            }
+            entries.push(line);
+        }
+        let closing = closing?;
+
+        let (fields, tables_start) = match format {
+            Format::Yaml => (parse_yaml(&entries), None),
+            Format::Toml => parse_toml(&entries),
+        };
+        // right after the last field, so that blank lines before a table stay
+        let end = match tables_start {
+            Some(tables_start) => fields
+                .last()
+                .map_or(tables_start, |(field, _)| field.range.end),
+            None => closing.start,
+        };
+
+        let mut metadata = Metadata::default();
+        let fields = fields
+            .into_iter()
+            .map(|(field, value)| {
+                metadata.add(&field.key, value);
+                field
+            })
+            .collect();
+
+        Some(Frontmatter {
+            format,
+            metadata,
+            fields,
+            end,
+            body_start: closing.end,
+            newline,
+        })
+    }
+
+    /// The content following the frontmatter.
+    pub fn body<'a>(&self, content: &'a str) -> &'a str {
+        &content[self.body_start..]
+    }
+
+    /// Returns `content` with the top-level field `key` set to `value`, or
+    /// removed if there is no value. Everything else is kept as it is.
+    pub fn with_field(&self, content: &str, key: &str, value: Option<&Value>) -> String {
+        let field = self
+            .fields
+            .iter()
+            .find(|field| field.key.eq_ignore_ascii_case(key));
+
+        let (range, written) = match (field, value) {
+            (Some(field), Some(value)) => (
+                field.range.clone(),
+                format_field(
+                    self.format,
+                    &field.key,
+                    value,
+                    field.list_indent.as_deref(),
+                    self.newline,
+                ),
+            ),
+            (Some(field), None) => (field.range.clone(), String::new()),
+            (None, Some(value)) => (
+                self.end..self.end,
+                format_field(self.format, key, value, None, self.newline),
+            ),
+            (None, None) => return content.to_string(),
+        };
+
+        let mut result = content.to_string();
+        result.replace_range(range, &written);
+        result
+    }
+}
+
+/// Returns `content` with the frontmatter field `key` set to `value`, or
+/// removed if there is no value. YAML frontmatter is added if the note has
+/// none yet.
+pub fn set_field(content: &str, key: &str, value: Option<&Value>) -> String {
+    if let Some(frontmatter) = Frontmatter::parse(content) {
+        return frontmatter.with_field(content, key, value);
+    }
+
+    match value {
+        Some(value) => {
+            let newline = if content.contains("\r\n") {
+                "\r\n"
+            } else {
+                "\n"
+            };
+            format!(
+                "---{newline}{}---{newline}{content}",
+                format_field(Format::Yaml, key, value, None, newline)
+            )
+        }
+        None => content.to_string(),
+    }
+}
+
+fn parse_yaml(lines: &[Line]) -> Vec<(Field, Value)> {
+    let mut fields: Vec<(Field, Vec<&str>)> = Vec::new();
+
+    for line in lines {
+        let text = line.text;
+        let trimmed = text.trim();
+        let continues = text.starts_with(char::is_whitespace) || text.starts_with('-');
+
+        if trimmed.is_empty() || (trimmed.starts_with('#') && !continues) {
+            continue;
+        }
+
+        if continues {
+            // anything indented belongs to the field above
+            if let Some((field, continuation)) = fields.last_mut() {
+                field.range.end = line.end;
+                if field.list_indent.is_none() && is_list_item(trimmed) {
+                    field.list_indent = Some(text[..text.len() - text.trim_start().len()].into());
+                }
+                continuation.push(text);
+            }
+        } else if let Some((key, rest)) = split_yaml_key(text) {
+            fields.push((
+                Field {
+                    key: unquote(key),
+                    range: line.start..line.end,
+                    list_indent: None,
+                },
+                vec![rest],
+            ));
+        }
+    }
+
+    fields
+        .into_iter()
+        .map(|(field, lines)| {
+            let value = yaml_value(lines[0].trim(), &lines[1..]);
+            (field, value)
+        })
+        .collect()
+}
+
+fn is_list_item(text: &str) -> bool {
+    text == "-" || text.starts_with("- ")
+}
+
+fn split_yaml_key(line: &str) -> Option<(&str, &str)> {
+    line.char_indices()
+        .filter(|(_, c)| *c == ':')
+        .map(|(index, _)| (&line[..index], &line[index + 1..]))
+        .find(|(_, rest)| rest.is_empty() || rest.starts_with(char::is_whitespace))
+}
+
+fn yaml_value(first: &str, continuation: &[&str]) -> Value {
+    let items = continuation
+        .iter()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with('#'));
+
+    if first.starts_with('[') {
+        let text = std::iter::once(first)
+            .chain(items)
+            .collect::<Vec<_>>()
+            .join(" ");
+        Value::List(parse_list(&text))
+    } else if first.is_empty() && continuation.iter().any(|line| is_list_item(line.trim())) {
+        Value::List(
+            items
+                .filter(|line| is_list_item(line))
+                .map(|line| parse_scalar(&line[1..]))
+                .collect(),
+        )
+    } else if first.starts_with('|') || first.starts_with('>') {
+        let separator = if first.starts_with('|') { "\n" } else { " " };
+        Value::Text(items.collect::<Vec<_>>().join(separator))
+    } else if first.is_empty() {
+        Value::Text(items.collect::<Vec<_>>().join(" "))
+    } else {
+        Value::Text(parse_scalar(first))
+    }
+}
+
+/// Parses the top-level fields of TOML frontmatter, also returning where
+/// the first table starts, if there is one.
+fn parse_toml(lines: &[Line]) -> (Vec<(Field, Value)>, Option<usize>) {
+    let mut fields = Vec::new();
+    let mut lines = lines.iter();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.text.trim();
+
+        if trimmed.starts_with('[') {
+            // tables don't hold top-level fields
+            return (fields, Some(line.start));
+        }
+        let Some((key, rest)) = trimmed.split_once('=') else {
+            continue;
+        };
+
+        let mut field = Field {
+            key: unquote(key.trim()),
+            range: line.start..line.end,
+            list_indent: None,
+        };
+        let mut text = rest.trim().to_string();
+
+        // arrays and strings may span several lines
+        while !is_complete(&text) {
+            let Some(line) = lines.next() else {
+                break;
+            };
+            field.range.end = line.end;
+            text.push('\n');
+            text.push_str(line.text.trim());
+        }
+
+        let value = if text.starts_with('[') {
+            Value::List(parse_list(&text))
+        } else if let Some(multiline) = text
+            .strip_prefix("\"\"\"")
+            .or_else(|| text.strip_prefix("'''"))
+        {
+            Value::Text(
+                multiline[..multiline.len().saturating_sub(3)]
+                    .trim()
+                    .to_string(),
+            )
+        } else {
+            Value::Text(parse_scalar(&text))
+        };
+
+        fields.push((field, value));
+    }
+
+    (fields, None)
+}
+
+fn is_complete(text: &str) -> bool {
+    if text.starts_with("\"\"\"") || text.starts_with("'''") {
+        return text.len() >= 6 && text.ends_with(&text[..3]);
+    }
+
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    for c in text.chars() {
+        match quote {
+            Some(q) => {
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' => depth += 1,
+                ']' => depth -= 1,
+                '#' => break,
+                _ => {}
+            },
+        }
+    }
+
+    depth <= 0
+}
+
+/// Parses a list like `[a, "b", 'c']`.
+fn parse_list(text: &str) -> Vec<String> {
+    let inner = text.trim().trim_start_matches('[');
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    let mut escaped = false;
+
+    for c in inner.chars() {
+        match quote {
+            Some(q) => {
+                current.push(c);
+                if escaped {
+                    escaped = false;
+                } else if c == '\\' && q == '"' {
+                    escaped = true;
+                } else if c == q {
+                    quote = None;
+                }
+            }
+            None => match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    current.push(c);
+                }
+                ',' => items.push(std::mem::take(&mut current)),
+                ']' => break,
+                _ => current.push(c),
+            },
+        }
+    }
+    items.push(current);
+
+    items
+        .iter()
+        .map(|item| parse_scalar(item))
+        .filter(|item| !item.is_empty())
+        .collect()
+}
+
+/// Parses a single, possibly quoted value, dropping trailing comments.
+fn parse_scalar(text: &str) -> String {
+    let text = text.trim();
+
+    if let Some(rest) = text.strip_prefix('"') {
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => break,
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(other) => value.push(other),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        value
+    } else if let Some(rest) = text.strip_prefix('\'') {
+        let mut value = String::new();
+        let mut chars = rest.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c == '\'' {
+                if chars.peek() == Some(&'\'') {
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            value.push(c);
+        }
+        value
+    } else {
+        match text.find(" #") {
+            Some(index) => text[..index].trim_end().to_string(),
+            None => text.to_string(),
+        }
+    }
+}
+
+fn unquote(key: &str) -> String {
+    let key = key.trim();
+    if key.starts_with('"') || key.starts_with('\'') {
+        parse_scalar(key)
+    } else {
+        key.to_string()
+    }
+}
+
+fn format_field(
+    format: Format,
+    key: &str,
+    value: &Value,
+    list_indent: Option<&str>,
+    newline: &str,
+) -> String {
+    match (format, value) {
+        (Format::Yaml, Value::Text(text)) => {
+            format!("{key}: {}{newline}", yaml_scalar(text, false))
+        }
+        (Format::Yaml, Value::List(items)) => match list_indent {
+            Some(indent) if !items.is_empty() => {
+                let mut written = format!("{key}:{newline}");
+                for item in items {
+                    written.push_str(&format!("{indent}- {}{newline}", yaml_scalar(item, false)));
+                }
+                written
+            }
+            _ => format!(
+                "{key}: [{}]{newline}",
+                items
+                    .iter()
+                    .map(|item| yaml_scalar(item, true))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        },
+        (Format::Toml, Value::Text(text)) => format!("{key} = {}{newline}", toml_string(text)),
+        (Format::Toml, Value::List(items)) => format!(
+            "{key} = [{}]{newline}",
+            items
+                .iter()
+                .map(|item| toml_string(item))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Writes `text` as a YAML scalar, quoting it only where needed.
+fn yaml_scalar(text: &str, in_list: bool) -> String {
+    let needs_quotes = text.is_empty()
+        || text.trim() != text
+        || text.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        || text.contains(": ")
+        || text.contains(" #")
+        || text.contains('\n')
+        || (in_list && text.contains([',', '[', ']', '{', '}']))
+        || text.parse::<f64>().is_ok()
+        || matches!(
+            text.to_lowercase().as_str(),
+            "true" | "false" | "yes" | "no" | "on" | "off" | "null" | "~"
+        );
+
+    if needs_quotes {
+        toml_string(text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Writes `text` as a double quoted string, which YAML reads the same way.
+fn toml_string(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YAML: &str = "---
+title: My Note # not part of the title
+tags:
+  - rust
+  - '#gtk'
+aliases: [first, \"second, really\"]
+created: 2024-05-01
+draft: true
+---
+# Heading
+";
+
+    #[test]
+    fn test_parse_yaml() {
+        let frontmatter = Frontmatter::parse(YAML).unwrap();
+
+        assert_eq!(frontmatter.format, Format::Yaml);
+        assert_eq!(
+            frontmatter.metadata,
+            Metadata {
+                title: Some("My Note".into()),
+                tags: vec!["rust".into(), "gtk".into()],
+                aliases: vec!["first".into(), "second, really".into()],
+                created: Some("2024-05-01".into()),
+                custom: vec![("draft".into(), Value::Text("true".into()))],
+            }
+        );
+        assert_eq!(frontmatter.body(YAML), "# Heading\n");
+    }
+
+    #[test]
+    fn test_parse_toml() {
+        let content = "+++\ntitle = \"A \\\"quoted\\\" title\"\ntags = [\n  \"a\",\n  \"b\",\n]\ndate = 2024-05-01T10:00:00Z\n\n[extra]\nkey = \"value\"\n+++\nbody";
+        let frontmatter = Frontmatter::parse(content).unwrap();
+
+        assert_eq!(frontmatter.format, Format::Toml);
+        assert_eq!(
+            frontmatter.metadata.title.as_deref(),
+            Some("A \"quoted\" title")
+        );
+        assert_eq!(frontmatter.metadata.tags, vec!["a", "b"]);
+        assert_eq!(
+            frontmatter.metadata.created.as_deref(),
+            Some("2024-05-01T10:00:00Z")
+        );
+        assert!(frontmatter.metadata.custom.is_empty());
+        assert_eq!(frontmatter.body(content), "body");
+    }
+
+    #[test]
+    fn test_no_frontmatter() {
+        assert!(Frontmatter::parse("# Heading\n---\n").is_none());
+        assert!(Frontmatter::parse("---\ntitle: unterminated\n").is_none());
+    }
+
+    #[test]
+    fn test_set_field_keeps_the_rest() {
+        let updated = set_field(YAML, "title", Some(&Value::Text("Other: title".into())));
+        assert_eq!(
+            updated,
+            YAML.replace(
+                "title: My Note # not part of the title",
+                "title: \"Other: title\""
+            )
+        );
+
+        let updated = set_field(
+            YAML,
+            "tags",
+            Some(&Value::List(vec!["rust".into(), "notes".into()])),
+        );
+        assert_eq!(updated, YAML.replace("  - '#gtk'", "  - notes"));
+
+        let updated = set_field(YAML, "aliases", None);
+        assert_eq!(
+            updated,
+            YAML.replace("aliases: [first, \"second, really\"]\n", "")
+        );
+    }
+
+    #[test]
+    fn test_set_field_adds_frontmatter() {
+        let tags = Value::List(vec!["a".into(), "b c".into()]);
+
+        assert_eq!(
+            set_field("body\r\n", "tags", Some(&tags)),
+            "---\r\ntags: [a, b c]\r\n---\r\nbody\r\n"
+        );
+        assert_eq!(
+            set_field("+++\ntitle = \"x\"\n+++\n", "tags", Some(&tags)),
+            "+++\ntitle = \"x\"\ntags = [\"a\", \"b c\"]\n+++\n"
+        );
+        assert_eq!(set_field("body", "tags", None), "body");
+    }
+
+    #[test]
+    fn test_set_field_before_toml_tables() {
+        let tags = Value::List(vec!["a".into()]);
+
+        assert_eq!(
+            set_field(
+                "+++\ntitle = \"x\"\n\n[extra]\nkey = 1\n+++\n",
+                "tags",
+                Some(&tags)
+            ),
+            "+++\ntitle = \"x\"\ntags = [\"a\"]\n\n[extra]\nkey = 1\n+++\n"
+        );
+        assert_eq!(
+            set_field("+++\n[extra]\nkey = 1\n+++\n", "tags", Some(&tags)),
+            "+++\ntags = [\"a\"]\n[extra]\nkey = 1\n+++\n"
+        );
+    }
+}
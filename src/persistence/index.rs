@@ -0,0 +1,945 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use gtk::gio::prelude::*;
+use gtk::glib::DateTime;
+use gtk::{gio, glib};
+
+use crate::LOG_DOMAIN;
+use crate::errors::Error;
+
+use super::links::{Link, note_links};
+use super::models::{AnyNote, CollectionPath, ItemKind};
+use super::storage::{self, ItemEvent, ItemStorage, SearchMatch};
+use super::tags::note_tags;
+use crate::util::markdown::WIKI_LINK_SCHEME;
+use crate::util::wiki;
+
+// bump whenever the tokenizer or the cache layout changes
+const CACHE_VERSION: u32 = 4;
+const MAX_AGE: Duration = Duration::from_secs(60);
+const MAX_RESULTS: usize = 100;
+const NAME_WEIGHT: u32 = 5;
+const PATH_WEIGHT: u32 = 2;
+
+/// location → (modification time, etag, content, term frequencies, tags, links)
+type CacheContent = HashMap<
+    String,
+    (
+        i64,
+        String,
+        String,
+        Vec<(String, u32)>,
+        Vec<String>,
+        Vec<String>,
+    ),
+>;
+
+/// What is remembered about a note's content between runs.
+#[derive(Clone)]
+struct CachedNote {
+    updated_at: i64,
+    etag: String,
+    /// the text, for showing matching lines without reading the note again
+    content: String,
+    terms: HashMap<String, u32>,
+    tags: Vec<String>,
+    /// locations of linked notes, and `wiki:` targets of wiki links
+    links: Vec<String>,
+}
+
+struct IndexedNote {
+    note: Box<dyn AnyNote>,
+    path: CollectionPath,
+    cached: CachedNote,
+}
+
+#[derive(Default)]
+struct IndexState {
+    cache_loaded: bool,
+    /// content of the cache file, only used until the first update is done
+    cached: HashMap<String, CachedNote>,
+    notes: HashMap<String, IndexedNote>,
+    /// term → location → weight
+    postings: HashMap<String, HashMap<String, u32>>,
+    updated_at: Option<Instant>,
+    stale: bool,
+}
+
+/// Inverted index over the names, paths, contents and tags of all notes in a
+/// notebook, which also knows how notes link to each other. It is kept in the
+/// user's cache directory, so that only notes changed since the last run need
+/// to be read again.
+pub struct SearchIndex {
+    cache_file: gio::File,
+    state: RefCell<IndexState>,
+    updating: Cell<bool>,
+}
+
+impl SearchIndex {
+    /// Creates the index for the notebook at `root_location`. Nothing is read
+    /// before the first `update`.
+    pub fn new(root_location: &str) -> Self {
+        let root_hash =
+            glib::compute_checksum_for_string(glib::ChecksumType::Sha256, root_location)
+                .expect("valid checksum");
+        let cache_file = gio::File::for_path(glib::user_cache_dir())
+            .child("notes")
+            .child(root_hash)
+            .child("index");
+
+        Self {
+            cache_file,
+            state: RefCell::new(IndexState::default()),
+            updating: Cell::new(false),
+        }
+    }
+
+    /// Tells the index that notes have changed, so it gets updated before the
+    /// next search.
+    pub fn mark_stale(&self) {
+        self.state.borrow_mut().stale = true;
+    }
+
+    /// Brings the index up to date, reading only notes whose modification
+    /// time changed. Does nothing if an update is already running.
+    pub async fn update(&self, storage: &dyn ItemStorage) -> Result<(), Error> {
+        if self.updating.replace(true) {
+            return Ok(());
+        }
+
+        let result = self.update_notes(storage).await;
+        self.updating.set(false);
+
+        result
+    }
+
+    /// Updates the index for a single change, e.g. one reported by a watch,
+    /// without looking at all other notes again. Changes to collections may
+    /// affect any number of notes, so they still update the whole index.
+    /// Returns whether the index changed.
+    pub async fn apply_event(
+        &self,
+        storage: &dyn ItemStorage,
+        event: &ItemEvent,
+    ) -> Result<bool, Error> {
+        // a running update may or may not see the change
+        if self.updating.get() || self.state.borrow().updated_at.is_none() {
+            self.mark_stale();
+            return Ok(false);
+        }
+
+        let (old_location, item) = match event {
+            ItemEvent::Created(item) | ItemEvent::Changed(item) => (item.location(), Some(item)),
+            ItemEvent::Deleted { location } => (location.clone(), None),
+            ItemEvent::Moved { location, item } => (location.clone(), Some(item)),
+        };
+        if item.is_some_and(|item| item.kind() == ItemKind::Collection) {
+            return self.update(storage).await.map(|()| true);
+        }
+
+        let note = match item.and_then(|item| item.as_note()) {
+            Some(note) => {
+                let known_path = self
+                    .state
+                    .borrow()
+                    .notes
+                    .get(&note.location())
+                    .map(|indexed_note| indexed_note.path.clone());
+                match known_path {
+                    Some(path) => Some((note, path)),
+                    None => find_note(storage, &note.location()).await?,
+                }
+            }
+            None => None,
+        };
+
+        let mut changed = false;
+        let indexed_note = match note {
+            Some((note, path)) => {
+                let location = note.location();
+                let updated_at = unix_usec(&note.updated_at());
+                // a moved note keeps its content
+                let previous = {
+                    let state = self.state.borrow();
+                    state
+                        .cached_note(&location)
+                        .or_else(|| state.cached_note(&old_location))
+                };
+                let cached = match previous {
+                    Some(previous) if previous.updated_at == updated_at => previous,
+                    previous => {
+                        changed = true;
+                        read_note(storage, note.as_ref(), updated_at, previous).await
+                    }
+                };
+                Some((location, IndexedNote { note, path, cached }))
+            }
+            None => None,
+        };
+
+        {
+            let mut state = self.state.borrow_mut();
+            let removed = state.remove_notes(&old_location);
+            changed |= removed
+                && indexed_note
+                    .as_ref()
+                    .is_none_or(|(location, _)| *location != old_location);
+            if let Some((location, indexed_note)) = indexed_note {
+                state.insert_note(location, indexed_note);
+            }
+        }
+
+        if changed && let Err(err) = self.save_cache().await {
+            glib::g_warning!(LOG_DOMAIN, "could not write search index cache: {}", err);
+        }
+
+        Ok(changed)
+    }
+
+    /// Returns the notes below the last collection of `scope` matching all
+    /// words of `query`, best matches first. Falls back to searching the
+    /// storage directly while the index is being built.
+    pub async fn search(
+        &self,
+        storage: &dyn ItemStorage,
+        query: &str,
+        scope: &CollectionPath,
+    ) -> Result<Vec<SearchMatch>, Error> {
+        if self.needs_update() {
+            self.update(storage).await?;
+        }
+        if self.state.borrow().updated_at.is_none() {
+            return storage.search(query, scope).await;
+        }
+
+        let terms = tokenize(query);
+        let ranked = self.state.borrow().rank(&terms, &scope.last().location());
+
+        let mut queries = vec![query];
+        queries.extend(terms.iter().map(String::as_str));
+        let state = self.state.borrow();
+
+        Ok(ranked
+            .into_iter()
+            .map(|(note, path)| state.snippet(note, path, &queries))
+            .collect())
+    }
+
+    /// Returns all tags in the notebook with the number of notes having
+    /// them, sorted by name.
+    pub async fn tags(&self, storage: &dyn ItemStorage) -> Result<Vec<(String, usize)>, Error> {
+        if self.needs_update() {
+            self.update(storage).await?;
+        }
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for indexed_note in self.state.borrow().notes.values() {
+            for tag in &indexed_note.cached.tags {
+                *counts.entry(tag.clone()).or_default() += 1;
+            }
+        }
+
+        let mut tags: Vec<(String, usize)> = counts.into_iter().collect();
+        tags.sort_by(|(lhs, _), (rhs, _)| {
+            lhs.to_lowercase()
+                .cmp(&rhs.to_lowercase())
+                .then(lhs.cmp(rhs))
+        });
+
+        Ok(tags)
+    }
+
+    /// Returns the notes tagged with `tag` in all collections.
+    pub async fn notes_with_tag(
+        &self,
+        storage: &dyn ItemStorage,
+        tag: &str,
+    ) -> Result<Vec<SearchMatch>, Error> {
+        if self.needs_update() {
+            self.update(storage).await?;
+        }
+
+        let mut tagged: Vec<(Box<dyn AnyNote>, CollectionPath)> = self
+            .state
+            .borrow()
+            .notes
+            .values()
+            .filter(|indexed_note| indexed_note.cached.tags.iter().any(|known| known == tag))
+            .map(|indexed_note| (indexed_note.note.clone(), indexed_note.path.clone()))
+            .collect();
+        tagged.sort_by_key(|(note, _)| note.location());
+
+        let inline_tag = format!("#{}", tag);
+        let state = self.state.borrow();
+
+        Ok(tagged
+            .into_iter()
+            .map(|(note, path)| state.snippet(note, path, &[&inline_tag, tag]))
+            .collect())
+    }
+
+    /// Returns the notes linking to `note` by markdown or wiki links, with
+    /// the lines containing the links.
+    pub async fn backlinks(
+        &self,
+        storage: &dyn ItemStorage,
+        note: &dyn AnyNote,
+    ) -> Result<Vec<SearchMatch>, Error> {
+        if self.needs_update() {
+            self.update(storage).await?;
+        }
+
+        let location = note.location();
+        let name = note.name();
+
+        let linking: Vec<(Box<dyn AnyNote>, CollectionPath, Vec<String>)> = {
+            let state = self.state.borrow();
+            let path = state
+                .notes
+                .get(&location)
+                .map(|indexed_note| indexed_note.path.names_below_root())
+                .unwrap_or_default();
+            let wiki_prefix = format!("{}:", WIKI_LINK_SCHEME);
+
+            let mut linking: Vec<_> = state
+                .notes
+                .values()
+                .filter(|indexed_note| indexed_note.note.location() != location)
+                .filter_map(|indexed_note| {
+                    // what to look for in the linking note to show the link
+                    let queries: Vec<String> = indexed_note
+                        .cached
+                        .links
+                        .iter()
+                        .filter_map(|link| match link.strip_prefix(&wiki_prefix) {
+                            Some(target) => {
+                                wiki::links_to(target, &name, &path).then(|| target.to_string())
+                            }
+                            None => (*link == location).then(|| name.clone()),
+                        })
+                        .collect();
+
+                    (!queries.is_empty()).then(|| {
+                        (
+                            indexed_note.note.clone(),
+                            indexed_note.path.clone(),
+                            queries,
+                        )
+                    })
+                })
+                .collect();
+            linking.sort_by_key(|(note, _, _)| note.location());
+            linking
+        };
+
+        let state = self.state.borrow();
+
+        Ok(linking
+            .into_iter()
+            .map(|(note, path, queries)| {
+                let queries: Vec<&str> = queries.iter().map(String::as_str).collect();
+                state.snippet(note, path, &queries)
+            })
+            .collect())
+    }
+
+    /// Returns all notes known to the index, sorted by location, without
+    /// updating it first.
+    pub fn notes(&self) -> Vec<(Box<dyn AnyNote>, CollectionPath)> {
+        let mut notes: Vec<(Box<dyn AnyNote>, CollectionPath)> = self
+            .state
+            .borrow()
+            .notes
+            .values()
+            .map(|indexed_note| (indexed_note.note.clone(), indexed_note.path.clone()))
+            .collect();
+        notes.sort_by_key(|(note, _)| note.location());
+
+        notes
+    }
+
+    fn needs_update(&self) -> bool {
+        let state = self.state.borrow();
+
+        state.stale
+            || state
+                .updated_at
+                .is_none_or(|updated_at| updated_at.elapsed() > MAX_AGE)
+    }
+
+    async fn update_notes(&self, storage: &dyn ItemStorage) -> Result<(), Error> {
+        if !self.state.borrow().cache_loaded {
+            let cached = self.load_cache().await;
+            let mut state = self.state.borrow_mut();
+            state.cached = cached;
+            state.cache_loaded = true;
+        }
+        self.state.borrow_mut().stale = false;
+
+        let found = storage
+            .list_notes(&CollectionPath::from(storage.root()))
+            .await?;
+
+        let mut changed = found.len() != self.state.borrow().indexed_count();
+        let mut notes = HashMap::new();
+        for (note, path) in found {
+            let location = note.location();
+            let updated_at = unix_usec(&note.updated_at());
+            let previous = self.state.borrow().cached_note(&location);
+
+            let cached = match previous {
+                Some(previous) if previous.updated_at == updated_at => previous,
+                previous => {
+                    changed = true;
+                    read_note(storage, note.as_ref(), updated_at, previous).await
+                }
+            };
+            notes.insert(location, IndexedNote { note, path, cached });
+        }
+
+        {
+            let mut state = self.state.borrow_mut();
+            state.replace_notes(notes);
+            state.updated_at = Some(Instant::now());
+        }
+
+        if changed {
+            // the cache only saves time, so failing to write it is no reason to fail
+            if let Err(err) = self.save_cache().await {
+                glib::g_warning!(LOG_DOMAIN, "could not write search index cache: {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn load_cache(&self) -> HashMap<String, CachedNote> {
+        let Ok((data, _)) = self.cache_file.load_contents_future().await else {
+            return HashMap::new();
+        };
+        let variant = glib::Variant::from_data::<(u32, CacheContent), _>(data);
+
+        match variant.get::<(u32, CacheContent)>() {
+            Some((CACHE_VERSION, content)) => content
+                .into_iter()
+                .map(
+                    |(location, (updated_at, etag, content, terms, tags, links))| {
+                        let terms = terms.into_iter().collect();
+                        (
+                            location,
+                            CachedNote {
+                                updated_at,
+                                etag,
+                                content,
+                                terms,
+                                tags,
+                                links,
+                            },
+                        )
+                    },
+                )
+                .collect(),
+            _ => HashMap::new(),
+        }
+    }
+
+    async fn save_cache(&self) -> Result<(), Error> {
+        let content: CacheContent = self
+            .state
+            .borrow()
+            .notes
+            .iter()
+            .map(|(location, indexed_note)| {
+                let cached = &indexed_note.cached;
+                let terms = cached
+                    .terms
+                    .iter()
+                    .map(|(term, count)| (term.clone(), *count))
+                    .collect();
+                (
+                    location.clone(),
+                    (
+                        cached.updated_at,
+                        cached.etag.clone(),
+                        cached.content.clone(),
+                        terms,
+                        cached.tags.clone(),
+                        cached.links.clone(),
+                    ),
+                )
+            })
+            .collect();
+        let data = (CACHE_VERSION, content).to_variant().data_as_bytes();
+
+        make_directory_with_parents(self.cache_file.parent().expect("cache file has a parent"))
+            .await?;
+        self.cache_file
+            .replace_contents_future(data, None, false, gio::FileCreateFlags::PRIVATE)
+            .await
+            .map_err(|(_, err)| err)?;
+
+        Ok(())
+    }
+}
+
+impl IndexState {
+    fn indexed_count(&self) -> usize {
+        if self.updated_at.is_some() {
+            self.notes.len()
+        } else {
+            self.cached.len()
+        }
+    }
+
+    fn cached_note(&self, location: &str) -> Option<CachedNote> {
+        match self.notes.get(location) {
+            Some(indexed_note) => Some(indexed_note.cached.clone()),
+            None => self.cached.get(location).cloned(),
+        }
+    }
+
+    fn replace_notes(&mut self, notes: HashMap<String, IndexedNote>) {
+        self.cached.clear();
+        self.notes.clear();
+        self.postings.clear();
+        for (location, indexed_note) in notes {
+            self.insert_note(location, indexed_note);
+        }
+    }
+
+    fn insert_note(&mut self, location: String, indexed_note: IndexedNote) {
+        for (term, weight) in weighted_terms(&indexed_note) {
+            self.postings
+                .entry(term)
+                .or_default()
+                .insert(location.clone(), weight);
+        }
+        self.notes.insert(location, indexed_note);
+    }
+
+    /// Forgets the note at `location`, or all notes inside the collection at
+    /// `location`. Returns whether there were any.
+    fn remove_notes(&mut self, location: &str) -> bool {
+        let prefix = format!("{}/", location);
+        let is_removed =
+            |note_location: &str| note_location == location || note_location.starts_with(&prefix);
+
+        let count = self.notes.len();
+        self.notes
+            .retain(|note_location, _| !is_removed(note_location));
+        if self.notes.len() == count {
+            return false;
+        }
+
+        for postings in self.postings.values_mut() {
+            postings.retain(|note_location, _| !is_removed(note_location));
+        }
+        self.postings.retain(|_, postings| !postings.is_empty());
+
+        true
+    }
+
+    /// Finds the first line of `note` containing one of `queries`, to show it
+    /// along with the note.
+    fn snippet(
+        &self,
+        note: Box<dyn AnyNote>,
+        path: CollectionPath,
+        queries: &[&str],
+    ) -> SearchMatch {
+        let content = self
+            .notes
+            .get(&note.location())
+            .map_or("", |indexed_note| indexed_note.cached.content.as_str());
+
+        queries
+            .iter()
+            .find_map(|query| SearchMatch::find(note.clone(), &path, content, query))
+            // matched by name, path or tag only
+            .unwrap_or(SearchMatch {
+                note,
+                path,
+                line_number: 0,
+                line: String::new(),
+            })
+    }
+
+    /// Scores notes by how often the query terms appear, weighing rare
+    /// terms higher. Query terms also match longer words starting with them.
+    fn rank(&self, terms: &[String], scope: &str) -> Vec<(Box<dyn AnyNote>, CollectionPath)> {
+        let scope = format!("{}/", scope.trim_end_matches('/'));
+
+        let mut scores: Option<HashMap<&str, f64>> = None;
+        for term in terms {
+            let mut term_scores: HashMap<&str, f64> = HashMap::new();
+            for (_, postings) in self
+                .postings
+                .iter()
+                .filter(|(indexed_term, _)| indexed_term.starts_with(term.as_str()))
+            {
+                let idf = (1.0 + self.notes.len() as f64 / postings.len() as f64).ln();
+                for (location, weight) in postings {
+                    *term_scores.entry(location.as_str()).or_default() += *weight as f64 * idf;
+                }
+            }
+
+            // all terms have to match
+            scores = Some(match scores {
+                None => term_scores,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(location, score)| {
+                        term_scores
+                            .get(location)
+                            .map(|term_score| (location, score + term_score))
+                    })
+                    .collect(),
+            });
+        }
+
+        let mut ranked: Vec<(&str, f64)> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(location, _)| location.starts_with(&scope))
+            .collect();
+        ranked.sort_by(|(lhs_location, lhs_score), (rhs_location, rhs_score)| {
+            rhs_score
+                .total_cmp(lhs_score)
+                .then(lhs_location.cmp(rhs_location))
+        });
+
+        ranked
+            .into_iter()
+            .take(MAX_RESULTS)
+            .filter_map(|(location, _)| self.notes.get(location))
+            .map(|indexed_note| (indexed_note.note.clone(), indexed_note.path.clone()))
+            .collect()
+    }
+}
+
+/// Creates `dir` along with any missing parents, like
+/// `gio::File::make_directory_with_parents` but without blocking.
+async fn make_directory_with_parents(dir: gio::File) -> Result<(), glib::Error> {
+    let mut missing = vec![dir];
+    while let Some(dir) = missing.last() {
+        match dir.make_directory_future(glib::Priority::DEFAULT).await {
+            Err(err) if err.matches(gio::IOErrorEnum::NotFound) => match dir.parent() {
+                Some(parent) => missing.push(parent),
+                None => return Err(err),
+            },
+            Err(err) if !err.matches(gio::IOErrorEnum::Exists) => return Err(err),
+            _ => {
+                missing.pop();
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Looks up the note at `location` along with the collections containing it,
+/// by walking down from the root collection.
+async fn find_note(
+    storage: &dyn ItemStorage,
+    location: &str,
+) -> Result<Option<(Box<dyn AnyNote>, CollectionPath)>, Error> {
+    let relative_path =
+        gio::File::for_uri(&storage.root().location()).relative_path(&gio::File::for_uri(location));
+    let Some(relative_path) = relative_path else {
+        return Ok(None);
+    };
+    let names: Vec<String> = relative_path
+        .iter()
+        .map(|name| name.to_string_lossy().to_string())
+        .collect();
+
+    Ok(storage::find_item(storage, &names)
+        .await?
+        .and_then(|(path, item)| Some((item.as_note()?, path))))
+}
+
+async fn read_note(
+    storage: &dyn ItemStorage,
+    note: &dyn AnyNote,
+    updated_at: i64,
+    previous: Option<CachedNote>,
+) -> CachedNote {
+    // the etag the note was listed with tells whether it changed without reading it
+    if let Some(previous) = &previous
+        && !previous.etag.is_empty()
+        && note.etag().is_some_and(|etag| etag == previous.etag)
+    {
+        return CachedNote {
+            updated_at,
+            ..previous.clone()
+        };
+    }
+
+    // notes that can't be read as text are found by name and path only
+    let Ok(content) = storage.load_content(note).await else {
+        return CachedNote {
+            updated_at,
+            etag: String::new(),
+            content: String::new(),
+            terms: HashMap::new(),
+            tags: Vec::new(),
+            links: Vec::new(),
+        };
+    };
+    let etag = content.etag.unwrap_or_default();
+
+    match previous {
+        Some(previous) if !etag.is_empty() && previous.etag == etag => CachedNote {
+            updated_at,
+            etag,
+            ..previous
+        },
+        _ => CachedNote {
+            updated_at,
+            etag,
+            terms: term_frequencies(&content.content),
+            tags: note_tags(&note.metadata(), &content.content),
+            links: resolve_links(&note.location(), note_links(&content.content)),
+            content: content.content,
+        },
+    }
+}
+
+/// Turns links into locations of notes, resolving relative ones against the
+/// location of the note they are in. Links to web pages are dropped.
+fn resolve_links(location: &str, links: Vec<Link>) -> Vec<String> {
+    links
+        .into_iter()
+        .filter_map(|link| match link {
+            Link::Wiki(target) => Some(format!("{}:{}", WIKI_LINK_SCHEME, target)),
+            Link::Url(url) => {
+                // links like `<my note.md>` aren't escaped yet
+                let escaped = glib::Uri::escape_string(&url, Some("!$&'()*+,;=:@/?#%~"), false);
+                let resolved =
+                    glib::Uri::resolve_relative(Some(location), &escaped, glib::UriFlags::NONE)
+                        .ok()?;
+                let resolved = resolved.split(['#', '?']).next().unwrap_or_default();
+
+                resolved
+                    .starts_with("file:")
+                    .then(|| gio::File::for_uri(resolved).uri().to_string())
+            }
+        })
+        .collect()
+}
+
+fn weighted_terms(indexed_note: &IndexedNote) -> HashMap<String, u32> {
+    let mut terms = indexed_note.cached.terms.clone();
+    for term in tokenize(&indexed_note.note.name()) {
+        *terms.entry(term).or_default() += NAME_WEIGHT;
+    }
+    // the root collection is part of every path, so it doesn't tell notes apart
+    for collection in indexed_note.path.iter().skip(1) {
+        for term in tokenize(&collection.name()) {
+            *terms.entry(term).or_default() += PATH_WEIGHT;
+        }
+    }
+
+    terms
+}
+
+fn unix_usec(date_time: &DateTime) -> i64 {
+    date_time.to_unix() * 1_000_000 + date_time.microsecond() as i64
+}
+
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn term_frequencies(text: &str) -> HashMap<String, u32> {
+    let mut terms = HashMap::new();
+    for term in tokenize(text) {
+        *terms.entry(term).or_default() += 1;
+    }
+
+    terms
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use super::*;
+    use crate::persistence::frontmatter::Metadata;
+    use crate::persistence::models::{AnyAttachment, AnyCollection, AnyItem};
+
+    /// A note or collection that only has a name and a location.
+    #[derive(Debug, Clone)]
+    struct TestItem {
+        kind: ItemKind,
+        location: String,
+    }
+
+    impl AnyItem for TestItem {
+        fn kind(&self) -> ItemKind {
+            self.kind
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_note(&self) -> Option<Box<dyn AnyNote>> {
+            Some(Box::new(self.clone()))
+        }
+
+        fn as_collection(&self) -> Option<Box<dyn AnyCollection>> {
+            Some(Box::new(self.clone()))
+        }
+
+        fn as_attachment(&self) -> Option<Box<dyn AnyAttachment>> {
+            None
+        }
+
+        fn clone_box(&self) -> Box<dyn AnyItem> {
+            Box::new(self.clone())
+        }
+
+        fn name(&self) -> String {
+            self.location
+                .rsplit('/')
+                .next()
+                .unwrap_or_default()
+                .to_string()
+        }
+
+        fn updated_at(&self) -> DateTime {
+            DateTime::now_utc().unwrap()
+        }
+
+        fn location(&self) -> String {
+            self.location.clone()
+        }
+    }
+
+    impl AnyNote for TestItem {
+        fn metadata(&self) -> Metadata {
+            Metadata::default()
+        }
+
+        fn etag(&self) -> Option<String> {
+            None
+        }
+    }
+
+    impl AnyCollection for TestItem {}
+
+    fn indexed_note(location: &str, content: &str) -> (String, IndexedNote) {
+        let collection = |location: &str| -> Box<dyn AnyCollection> {
+            Box::new(TestItem {
+                kind: ItemKind::Collection,
+                location: location.to_string(),
+            })
+        };
+        let mut path = CollectionPath::from(collection("/notes"));
+        let (parent, _) = location.rsplit_once('/').unwrap();
+        if parent != "/notes" {
+            path.push(collection(parent));
+        }
+
+        let note = Box::new(TestItem {
+            kind: ItemKind::Note,
+            location: location.to_string(),
+        });
+        let cached = CachedNote {
+            updated_at: 0,
+            etag: String::new(),
+            content: content.to_string(),
+            terms: term_frequencies(content),
+            tags: Vec::new(),
+            links: Vec::new(),
+        };
+
+        (location.to_string(), IndexedNote { note, path, cached })
+    }
+
+    #[test]
+    fn test_remove_notes_inside_collection() {
+        let mut state = IndexState::default();
+        state.replace_notes(HashMap::from([
+            indexed_note("/notes/budget.md", "numbers"),
+            indexed_note("/notes/budget/2024.md", "numbers"),
+            indexed_note("/notes/budget-old.md", "numbers"),
+        ]));
+
+        assert!(state.remove_notes("/notes/budget"));
+        assert!(!state.remove_notes("/notes/budget"));
+
+        let mut ranked: Vec<String> = state
+            .rank(&["numbers".to_string()], "/notes")
+            .into_iter()
+            .map(|(note, _)| note.location())
+            .collect();
+        ranked.sort();
+        assert_eq!(ranked, vec!["/notes/budget-old.md", "/notes/budget.md"]);
+        assert!(!state.postings.contains_key("2024"));
+    }
+
+    #[test]
+    fn test_rank_by_name_path_and_content() {
+        let mut state = IndexState::default();
+        state.replace_notes(HashMap::from([
+            indexed_note("/notes/garden.md", "the budget for seeds"),
+            indexed_note("/notes/budget/2024.md", "numbers"),
+            indexed_note("/notes/budget.md", "numbers"),
+            indexed_note("/notes/recipes.md", "no match"),
+        ]));
+
+        let ranked: Vec<String> = state
+            .rank(&["budget".to_string()], "/notes")
+            .into_iter()
+            .map(|(note, _)| note.location())
+            .collect();
+
+        // weighted NAME_WEIGHT, PATH_WEIGHT and once in the content
+        assert_eq!(
+            ranked,
+            vec![
+                "/notes/budget.md",
+                "/notes/budget/2024.md",
+                "/notes/garden.md"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_snippet_from_indexed_content() {
+        let mut state = IndexState::default();
+        state.replace_notes(HashMap::from([indexed_note(
+            "/notes/garden.md",
+            "# Garden\n\n  The Budget for seeds\n",
+        )]));
+        let (note, path) = state.rank(&["budget".to_string()], "/notes").remove(0);
+
+        let snippet = state.snippet(note, path, &["budget for", "budget"]);
+        assert_eq!(snippet.line_number, 2);
+        assert_eq!(snippet.line, "The Budget for seeds");
+    }
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("# Über-Notes: 2 files, one_dir"),
+            vec!["über", "notes", "2", "files", "one", "dir"]
+        );
+    }
+
+    #[test]
+    fn test_term_frequencies() {
+        let terms = term_frequencies("a note, another Note");
+
+        assert_eq!(terms.get("note"), Some(&2));
+        assert_eq!(terms.get("another"), Some(&1));
+        assert_eq!(terms.get("notes"), None);
+    }
+}
@@ -0,0 +1,80 @@
+use markdown::{ParseOptions, mdast::Node, to_mdast};
+
+use crate::util::wiki;
+
+use super::frontmatter::Frontmatter;
+
+/// A link in a note, as written.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Link {
+    /// target of a `[[wiki link]]`
+    Wiki(String),
+    /// destination of a markdown link or link definition
+    Url(String),
+}
+
+/// Returns the links in a note, leaving out images and anything in code.
+pub fn note_links(content: &str) -> Vec<Link> {
+    let body = match Frontmatter::parse(content) {
+        Some(frontmatter) => frontmatter.body(content),
+        None => content,
+    };
+
+    let mut links = Vec::new();
+    if let Ok(root) = to_mdast(body, &ParseOptions::default()) {
+        collect_links(&root, &mut links);
+    }
+
+    links
+}
+
+fn collect_links(node: &Node, links: &mut Vec<Link>) {
+    match node {
+        Node::Link(link) => links.push(Link::Url(link.url.clone())),
+        Node::Definition(definition) => links.push(Link::Url(definition.url.clone())),
+        Node::Text(text) => {
+            let mut rest = text.value.as_str();
+            while let Some(start) = rest.find("[[") {
+                let Some(length) = rest[start + 2..].find("]]") else {
+                    break;
+                };
+                let (target, _) = wiki::parse_link(&rest[start + 2..start + 2 + length]);
+                links.push(Link::Wiki(target.to_string()));
+                rest = &rest[start + 2 + length + 2..];
+            }
+        }
+        _ => {}
+    }
+
+    for child in node.children().into_iter().flatten() {
+        collect_links(child, links);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_note_links() {
+        let content = "---
+related: \"[[not a link]]\"
+---
+See [the plan](../projects/plan.md#goals), [[Ideas|my ideas]] and *[[Other]]*.
+
+![image](image.png) `[[code]]` [ref]
+
+[ref]: <notes/with space.md>
+";
+
+        assert_eq!(
+            note_links(content),
+            vec![
+                Link::Url("../projects/plan.md#goals".into()),
+                Link::Wiki("Ideas".into()),
+                Link::Wiki("Other".into()),
+                Link::Url("notes/with space.md".into()),
+            ]
+        );
+    }
+}
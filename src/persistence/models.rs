@@ -1,8 +1,10 @@
 use std::slice::Iter;
+use std::sync::{Arc, Mutex};
 use std::{any::Any, marker::PhantomData};
 
 use gtk::glib::DateTime;
 
+use super::frontmatter::Metadata;
 use super::storage::StorageBackend;
 
 pub trait Meta: Send {}
@@ -33,7 +35,14 @@ impl PartialEq for &dyn AnyItem {
     }
 }
 
-pub trait AnyNote: AnyItem {}
+pub trait AnyNote: AnyItem {
+    /// What the note's frontmatter says, as of the last time it was loaded or
+    /// saved. Empty before that.
+    fn metadata(&self) -> Metadata;
+    /// Etag of the note's content as of when it was listed, if the storage
+    /// tells it without loading the content.
+    fn etag(&self) -> Option<String>;
+}
 
 pub trait AnyCollection: AnyItem {}
 
@@ -87,6 +96,10 @@ pub struct Note<S: StorageBackend> {
     name: String,
     updated_at: DateTime,
     location: String,
+    etag: Option<String>,
+    /// shared by all copies of the note, so that they all see it once the
+    /// note has been loaded
+    metadata: Arc<Mutex<Metadata>>,
     _marker: PhantomData<S>,
 }
 
@@ -106,9 +119,20 @@ impl<S: StorageBackend + 'static + Send> Note<S> {
             name,
             updated_at,
             location,
+            etag: None,
+            metadata: Arc::default(),
             _marker: PhantomData,
         }
     }
+
+    pub(super) fn with_etag(mut self, etag: Option<String>) -> Self {
+        self.etag = etag;
+        self
+    }
+
+    pub(super) fn set_metadata(&self, metadata: Metadata) {
+        *self.metadata.lock().unwrap() = metadata;
+    }
 }
 
 impl<S: StorageBackend + 'static + Send> AnyItem for Note<S>
@@ -129,6 +153,8 @@ where
             name: self.name.clone(),
             updated_at: self.updated_at.clone(),
             location: self.location.clone(),
+            etag: self.etag.clone(),
+            metadata: self.metadata.clone(),
             _marker: PhantomData,
         }))
     }
@@ -158,7 +184,15 @@ where
     }
 }
 
-impl<S: StorageBackend + 'static + Send> AnyNote for Note<S> {}
+impl<S: StorageBackend + 'static + Send> AnyNote for Note<S> {
+    fn metadata(&self) -> Metadata {
+        self.metadata.lock().unwrap().clone()
+    }
+
+    fn etag(&self) -> Option<String> {
+        self.etag.clone()
+    }
+}
 
 impl<S: StorageBackend> std::fmt::Debug for Note<S> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -176,7 +210,6 @@ pub struct Collection<S: StorageBackend> {
 }
 
 impl<S: StorageBackend + 'static + Send> Collection<S> {
-    #[allow(dead_code)]
     pub fn from_any(collection: &dyn AnyCollection) -> Option<&Collection<S>> {
         collection.as_any().downcast_ref::<Collection<S>>()
     }
@@ -347,6 +380,38 @@ impl<S: StorageBackend> std::fmt::Debug for Attachment<S> {
     }
 }
 
+/// A note, collection or attachment of a particular storage backend, for
+/// operations that work on any kind of item.
+pub enum TypedItem<'a, S: StorageBackend> {
+    Note(&'a Note<S>),
+    Collection(&'a Collection<S>),
+    Attachment(&'a Attachment<S>),
+}
+
+impl<'a, S: StorageBackend + 'static + Send> TypedItem<'a, S> {
+    pub fn from_any(item: &'a dyn AnyItem) -> Option<Self> {
+        let item = item.as_any();
+        item.downcast_ref::<Note<S>>()
+            .map(TypedItem::Note)
+            .or_else(|| {
+                item.downcast_ref::<Collection<S>>()
+                    .map(TypedItem::Collection)
+            })
+            .or_else(|| {
+                item.downcast_ref::<Attachment<S>>()
+                    .map(TypedItem::Attachment)
+            })
+    }
+
+    pub fn location(&self) -> String {
+        match self {
+            TypedItem::Note(note) => note.location.clone(),
+            TypedItem::Collection(collection) => collection.location.clone(),
+            TypedItem::Attachment(attachment) => attachment.location.clone(),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct CollectionPath {
     collections: Vec<Box<dyn AnyCollection>>,
@@ -381,6 +446,29 @@ impl CollectionPath {
     pub fn last(&self) -> &Box<dyn AnyCollection> {
         self.collections.last().unwrap()
     }
+
+    /// Names of the collections following the root collection.
+    pub fn names_below_root(&self) -> Vec<String> {
+        self.collections
+            .iter()
+            .skip(1)
+            .map(|collection| collection.name())
+            .collect()
+    }
+
+    pub fn position(&self, collection: &dyn AnyCollection) -> Option<usize> {
+        self.collections
+            .iter()
+            .position(|c| c.location() == collection.location())
+    }
+
+    pub fn truncate(&mut self, len: usize) {
+        if len == 0 {
+            panic!("need a root collection")
+        }
+
+        self.collections.truncate(len);
+    }
 }
 
 impl PartialEq for CollectionPath {
@@ -470,6 +558,61 @@ mod tests {
 
     impl AnyCollection for TestCollection {}
 
+    /// A collection with a location of its own, for telling collections apart
+    /// by location.
+    #[derive(Debug, Clone)]
+    struct LocatedCollection {
+        name: String,
+    }
+
+    impl LocatedCollection {
+        fn new(name: &str) -> Self {
+            Self {
+                name: name.to_string(),
+            }
+        }
+    }
+
+    impl AnyItem for LocatedCollection {
+        fn kind(&self) -> ItemKind {
+            ItemKind::Collection
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn updated_at(&self) -> DateTime {
+            DateTime::from_utc(2025, 6, 16, 11, 30, 0.0).unwrap()
+        }
+
+        fn location(&self) -> String {
+            format!("/somewhere/over/the/filesystem/{}", self.name)
+        }
+
+        fn clone_box(&self) -> Box<dyn AnyItem> {
+            Box::new(self.clone())
+        }
+
+        fn as_note(&self) -> Option<Box<dyn AnyNote>> {
+            None
+        }
+
+        fn as_collection(&self) -> Option<Box<dyn AnyCollection>> {
+            Some(Box::new(self.clone()))
+        }
+
+        fn as_attachment(&self) -> Option<Box<dyn AnyAttachment>> {
+            None
+        }
+    }
+
+    impl AnyCollection for LocatedCollection {}
+
     impl PartialEq for TestCollection {
         fn eq(&self, other: &Self) -> bool {
             self.name == other.name
@@ -507,4 +650,38 @@ mod tests {
 
         assert!(path.parent().is_none());
     }
+
+    #[test]
+    fn collection_path_position_finds_collection_by_location() {
+        let path = CollectionPath::new(vec![
+            Box::new(LocatedCollection::new("a")),
+            Box::new(LocatedCollection::new("b")),
+        ]);
+
+        assert_eq!(path.position(&LocatedCollection::new("b")), Some(1));
+        assert_eq!(path.position(&LocatedCollection::new("c")), None);
+    }
+
+    #[test]
+    fn collection_path_truncate_drops_descendants() {
+        let mut path = CollectionPath::new(vec![
+            Box::new(TestCollection::new(String::from("a"))),
+            Box::new(TestCollection::new(String::from("b"))),
+            Box::new(TestCollection::new(String::from("c"))),
+        ]);
+
+        path.truncate(1);
+
+        assert!(
+            path == CollectionPath::new(vec![Box::new(TestCollection::new(String::from("a")))])
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "need a root collection")]
+    fn collection_path_truncate_keeps_root() {
+        let mut path = CollectionPath::new(vec![Box::new(TestCollection::new(String::from("a")))]);
+
+        path.truncate(0);
+    }
 }
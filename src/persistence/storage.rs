@@ -1,16 +1,130 @@
 use async_trait::async_trait;
+use gtk::gio;
 
-use crate::errors::Error;
+use crate::errors::{Error, Operation};
 
-use super::models::{AnyCollection, AnyItem, AnyNote, Collection, CollectionPath, Meta, Note};
+use super::frontmatter::Metadata;
+use super::models::{
+    AnyAttachment, AnyCollection, AnyItem, AnyNote, Collection, CollectionPath, Meta, Note,
+    TypedItem,
+};
+use super::text_format::TextFormat;
 
 pub struct NoteContent {
     pub content: String,
     pub etag: Option<String>,
+    /// encoding and line endings of the stored note
+    pub format: TextFormat,
+}
+
+/// Details about an attachment that aren't needed for listing it.
+#[derive(Debug, Clone)]
+pub struct AttachmentInfo {
+    /// size in bytes
+    pub size: u64,
+    pub content_type: String,
+}
+
+/// A note whose content matches a search query.
+#[derive(Debug, Clone)]
+pub struct SearchMatch {
+    pub note: Box<dyn AnyNote>,
+    /// collections leading to the note, starting with the root collection
+    pub path: CollectionPath,
+    /// the first matching line, counting from zero
+    pub line_number: usize,
+    pub line: String,
+}
+
+impl SearchMatch {
+    /// Looks for the first line of `content` containing `query`, ignoring case.
+    pub fn find(
+        note: Box<dyn AnyNote>,
+        path: &CollectionPath,
+        content: &str,
+        query: &str,
+    ) -> Option<SearchMatch> {
+        let query = query.to_lowercase();
+
+        content
+            .lines()
+            .enumerate()
+            .find(|(_, line)| line.to_lowercase().contains(&query))
+            .map(|(line_number, line)| SearchMatch {
+                note,
+                path: path.clone(),
+                line_number,
+                line: line.trim().to_string(),
+            })
+    }
+}
+
+/// A change to an item made by someone else, e.g. another editor or a sync
+/// tool.
+#[derive(Debug, Clone)]
+pub enum ItemEvent {
+    Created(Box<dyn AnyItem>),
+    Changed(Box<dyn AnyItem>),
+    Deleted {
+        location: String,
+    },
+    Moved {
+        location: String,
+        item: Box<dyn AnyItem>,
+    },
+}
+
+/// Handle for watching items for changes. Watching stops when it is dropped.
+pub struct Watch {
+    cancel: Option<Box<dyn FnOnce()>>,
+}
+
+impl Watch {
+    pub fn new(cancel: impl FnOnce() + 'static) -> Self {
+        Self {
+            cancel: Some(Box::new(cancel)),
+        }
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        if let Some(cancel) = self.cancel.take() {
+            cancel();
+        }
+    }
+}
+
+/// Looks up the item reached by following `names` from the root collection,
+/// returning it along with the collections containing it.
+pub async fn find_item(
+    storage: &dyn ItemStorage,
+    names: &[String],
+) -> Result<Option<(CollectionPath, Box<dyn AnyItem>)>, Error> {
+    let mut path = CollectionPath::from(storage.root());
+    let mut names = names.iter().peekable();
+    while let Some(name) = names.next() {
+        let items = storage.list_items(&path).await?;
+        let Some(item) = items.into_iter().find(|item| &item.name() == name) else {
+            return Ok(None);
+        };
+
+        if names.peek().is_none() {
+            return Ok(Some((path, item)));
+        }
+        match item.as_collection() {
+            Some(collection) => path.push(collection),
+            None => return Ok(None),
+        }
+    }
+
+    Ok(None)
 }
 
 // backend marker trait
 pub trait StorageBackend {
+    const NAME: &'static str;
+
     type NoteMeta: Meta + std::fmt::Debug + Clone + 'static;
     type CollectionMeta: Meta + std::fmt::Debug + Clone + 'static;
     type AttachmentMeta: Meta + std::fmt::Debug + Clone + 'static;
@@ -21,9 +135,62 @@ pub trait StorageBackend {
 pub trait TypedItemStorage<S: StorageBackend>: Send + Sync {
     fn root(&self) -> Box<Collection<S>>;
     async fn list_items(&self, path: &CollectionPath) -> Result<Vec<Box<dyn AnyItem>>, Error>;
+    async fn create_note(
+        &self,
+        collection: &Collection<S>,
+        name: &str,
+        initial_content: &str,
+    ) -> Result<Box<dyn AnyNote>, Error>;
     async fn rename_note(&self, note: &Note<S>, new_name: &str) -> Result<Box<dyn AnyNote>, Error>;
+    async fn create_collection(
+        &self,
+        parent: &Collection<S>,
+        name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error>;
+    async fn rename_collection(
+        &self,
+        collection: &Collection<S>,
+        new_name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error>;
+    async fn delete_collection(
+        &self,
+        collection: &Collection<S>,
+        recursive: bool,
+    ) -> Result<(), Error>;
+    /// Moves an item to the trash, or deletes it for good if `permanently` is
+    /// set. Fails with `Error::TrashNotSupported` if there is no trash.
+    async fn delete_item(&self, item: TypedItem<'_, S>, permanently: bool) -> Result<(), Error>;
+    /// Restores an item that has been moved to the trash by `delete_item`.
+    async fn restore_item(&self, item: TypedItem<'_, S>) -> Result<(), Error>;
+    async fn move_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &Collection<S>,
+    ) -> Result<Box<dyn AnyItem>, Error>;
+    async fn copy_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &Collection<S>,
+    ) -> Result<Box<dyn AnyItem>, Error>;
     async fn load_content(&self, note: &Note<S>) -> Result<NoteContent, Error>;
+    async fn load_bytes(&self, item: &dyn AnyItem) -> Result<Vec<u8>, Error>;
+    async fn read_stream(&self, item: &dyn AnyItem) -> Result<gio::InputStream, Error>;
+    async fn attachment_info(
+        &self,
+        attachment: &dyn AnyAttachment,
+    ) -> Result<AttachmentInfo, Error>;
     async fn save_content(&self, note: &Note<S>, content: &NoteContent) -> Result<String, Error>;
+    async fn list_notes(
+        &self,
+        path: &CollectionPath,
+    ) -> Result<Vec<(Box<dyn AnyNote>, CollectionPath)>, Error>;
+    async fn search(&self, query: &str, scope: &CollectionPath) -> Result<Vec<SearchMatch>, Error>;
+    fn watch_collection(
+        &self,
+        collection: &Collection<S>,
+        on_event: Box<dyn Fn(ItemEvent)>,
+    ) -> Result<Watch, Error>;
+    fn watch_note(&self, note: &Note<S>, on_event: Box<dyn Fn(ItemEvent)>) -> Result<Watch, Error>;
 }
 
 // type-erased storage
@@ -31,17 +198,86 @@ pub trait TypedItemStorage<S: StorageBackend>: Send + Sync {
 pub trait ItemStorage {
     fn root(&self) -> Box<dyn AnyCollection>;
     async fn list_items(&self, path: &CollectionPath) -> Result<Vec<Box<dyn AnyItem>>, Error>;
+    async fn create_note(
+        &self,
+        collection: &dyn AnyCollection,
+        name: &str,
+        initial_content: &str,
+    ) -> Result<Box<dyn AnyNote>, Error>;
     async fn rename_note(
         &self,
         note: &dyn AnyNote,
         new_name: &str,
     ) -> Result<Box<dyn AnyNote>, Error>;
+    async fn create_collection(
+        &self,
+        parent: &dyn AnyCollection,
+        name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error>;
+    async fn rename_collection(
+        &self,
+        collection: &dyn AnyCollection,
+        new_name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error>;
+    async fn delete_collection(
+        &self,
+        collection: &dyn AnyCollection,
+        recursive: bool,
+    ) -> Result<(), Error>;
+    /// Moves an item to the trash, or deletes it for good if `permanently` is
+    /// set. Fails with `Error::TrashNotSupported` if there is no trash.
+    async fn delete_item(&self, item: &dyn AnyItem, permanently: bool) -> Result<(), Error>;
+    /// Restores an item that has been moved to the trash by `delete_item`.
+    async fn restore_item(&self, item: &dyn AnyItem) -> Result<(), Error>;
+    async fn move_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &dyn AnyCollection,
+    ) -> Result<Box<dyn AnyItem>, Error>;
+    /// Copies an item into `destination`; collections are copied with all
+    /// their contents.
+    async fn copy_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &dyn AnyCollection,
+    ) -> Result<Box<dyn AnyItem>, Error>;
     async fn load_content(&self, note: &dyn AnyNote) -> Result<NoteContent, Error>;
+    /// Loads the raw content of a note or attachment.
+    async fn load_bytes(&self, item: &dyn AnyItem) -> Result<Vec<u8>, Error>;
+    /// Opens a note or attachment for reading it bit by bit, e.g. for
+    /// playing a video without loading all of it.
+    async fn read_stream(&self, item: &dyn AnyItem) -> Result<gio::InputStream, Error>;
+    async fn attachment_info(
+        &self,
+        attachment: &dyn AnyAttachment,
+    ) -> Result<AttachmentInfo, Error>;
     async fn save_content(
         &self,
         note: &dyn AnyNote,
         content: &NoteContent,
     ) -> Result<String, Error>;
+    /// Lists the notes in the last collection of `path` and the collections
+    /// below it, along with the collections containing them. Collections that
+    /// can't be read, or that might lead back up, are left out.
+    async fn list_notes(
+        &self,
+        path: &CollectionPath,
+    ) -> Result<Vec<(Box<dyn AnyNote>, CollectionPath)>, Error>;
+    /// Searches the contents of all notes in the last collection of `scope`
+    /// and the collections below it.
+    async fn search(&self, query: &str, scope: &CollectionPath) -> Result<Vec<SearchMatch>, Error>;
+    /// Calls `on_event` whenever items inside `collection` are changed.
+    fn watch_collection(
+        &self,
+        collection: &dyn AnyCollection,
+        on_event: Box<dyn Fn(ItemEvent)>,
+    ) -> Result<Watch, Error>;
+    /// Calls `on_event` whenever `note` is changed, moved or deleted.
+    fn watch_note(
+        &self,
+        note: &dyn AnyNote,
+        on_event: Box<dyn Fn(ItemEvent)>,
+    ) -> Result<Watch, Error>;
 }
 
 // type-erased wrapper for typed storage
@@ -49,6 +285,12 @@ pub(super) struct DynItemStorage<S: StorageBackend> {
     pub inner: Box<dyn TypedItemStorage<S> + Send + Sync>,
 }
 
+impl<S: StorageBackend> DynItemStorage<S> {
+    fn context(operation: Operation, location: String) -> impl FnOnce(Error) -> Error {
+        move |err| err.context(operation, location, S::NAME)
+    }
+}
+
 #[async_trait(?Send)]
 impl<S: StorageBackend + 'static + Send> ItemStorage for DynItemStorage<S> {
     fn root(&self) -> Box<dyn AnyCollection> {
@@ -56,11 +298,29 @@ impl<S: StorageBackend + 'static + Send> ItemStorage for DynItemStorage<S> {
     }
 
     async fn list_items(&self, path: &CollectionPath) -> Result<Vec<Box<dyn AnyItem>>, Error> {
-        let typed_items: Vec<Box<dyn AnyItem>> = self.inner.list_items(path).await?;
+        let typed_items: Vec<Box<dyn AnyItem>> = self
+            .inner
+            .list_items(path)
+            .await
+            .map_err(Self::context(Operation::ListItems, path.last().location()))?;
 
         Ok(typed_items)
     }
 
+    async fn create_note(
+        &self,
+        collection: &dyn AnyCollection,
+        name: &str,
+        initial_content: &str,
+    ) -> Result<Box<dyn AnyNote>, Error> {
+        let collection = Collection::<S>::from_any(collection).unwrap();
+
+        self.inner
+            .create_note(collection, name, initial_content)
+            .await
+            .map_err(Self::context(Operation::CreateNote, collection.location()))
+    }
+
     async fn rename_note(
         &self,
         note: &dyn AnyNote,
@@ -68,24 +328,196 @@ impl<S: StorageBackend + 'static + Send> ItemStorage for DynItemStorage<S> {
     ) -> Result<Box<dyn AnyNote>, Error> {
         let note = Note::<S>::from_any(note).unwrap();
 
-        Ok(self.inner.rename_note(note, new_name).await?)
+        self.inner
+            .rename_note(note, new_name)
+            .await
+            .map_err(Self::context(Operation::RenameNote, note.location()))
+    }
+
+    async fn create_collection(
+        &self,
+        parent: &dyn AnyCollection,
+        name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error> {
+        let parent = Collection::<S>::from_any(parent).unwrap();
+
+        self.inner
+            .create_collection(parent, name)
+            .await
+            .map_err(Self::context(
+                Operation::CreateCollection,
+                parent.location(),
+            ))
+    }
+
+    async fn rename_collection(
+        &self,
+        collection: &dyn AnyCollection,
+        new_name: &str,
+    ) -> Result<Box<dyn AnyCollection>, Error> {
+        let collection = Collection::<S>::from_any(collection).unwrap();
+
+        self.inner
+            .rename_collection(collection, new_name)
+            .await
+            .map_err(Self::context(
+                Operation::RenameCollection,
+                collection.location(),
+            ))
+    }
+
+    async fn delete_collection(
+        &self,
+        collection: &dyn AnyCollection,
+        recursive: bool,
+    ) -> Result<(), Error> {
+        let collection = Collection::<S>::from_any(collection).unwrap();
+
+        self.inner
+            .delete_collection(collection, recursive)
+            .await
+            .map_err(Self::context(
+                Operation::DeleteCollection,
+                collection.location(),
+            ))
+    }
+
+    async fn delete_item(&self, item: &dyn AnyItem, permanently: bool) -> Result<(), Error> {
+        let item = TypedItem::<S>::from_any(item).unwrap();
+        let location = item.location();
+
+        self.inner
+            .delete_item(item, permanently)
+            .await
+            .map_err(Self::context(Operation::DeleteItem, location))
+    }
+
+    async fn restore_item(&self, item: &dyn AnyItem) -> Result<(), Error> {
+        let item = TypedItem::<S>::from_any(item).unwrap();
+        let location = item.location();
+
+        self.inner
+            .restore_item(item)
+            .await
+            .map_err(Self::context(Operation::RestoreItem, location))
+    }
+
+    async fn move_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &dyn AnyCollection,
+    ) -> Result<Box<dyn AnyItem>, Error> {
+        let destination = Collection::<S>::from_any(destination).unwrap();
+
+        self.inner
+            .move_item(item, destination)
+            .await
+            .map_err(Self::context(Operation::MoveItem, item.location()))
+    }
+
+    async fn copy_item(
+        &self,
+        item: &dyn AnyItem,
+        destination: &dyn AnyCollection,
+    ) -> Result<Box<dyn AnyItem>, Error> {
+        let destination = Collection::<S>::from_any(destination).unwrap();
+
+        self.inner
+            .copy_item(item, destination)
+            .await
+            .map_err(Self::context(Operation::CopyItem, item.location()))
     }
 
     async fn load_content(&self, note: &dyn AnyNote) -> Result<NoteContent, Error> {
         let note = Note::<S>::from_any(note).unwrap();
-        let content: NoteContent = self.inner.load_content(note).await?;
+        let content: NoteContent = self
+            .inner
+            .load_content(note)
+            .await
+            .map_err(Self::context(Operation::LoadContent, note.location()))?;
+        note.set_metadata(Metadata::parse(&content.content));
 
         Ok(content)
     }
 
+    async fn load_bytes(&self, item: &dyn AnyItem) -> Result<Vec<u8>, Error> {
+        self.inner
+            .load_bytes(item)
+            .await
+            .map_err(Self::context(Operation::LoadBytes, item.location()))
+    }
+
+    async fn read_stream(&self, item: &dyn AnyItem) -> Result<gio::InputStream, Error> {
+        self.inner
+            .read_stream(item)
+            .await
+            .map_err(Self::context(Operation::LoadBytes, item.location()))
+    }
+
+    async fn attachment_info(
+        &self,
+        attachment: &dyn AnyAttachment,
+    ) -> Result<AttachmentInfo, Error> {
+        self.inner
+            .attachment_info(attachment)
+            .await
+            .map_err(Self::context(Operation::LoadBytes, attachment.location()))
+    }
+
     async fn save_content(
         &self,
         note: &dyn AnyNote,
         content: &NoteContent,
     ) -> Result<String, Error> {
         let note = Note::<S>::from_any(note).unwrap();
-        let etag = self.inner.save_content(note, content).await?;
+        let etag = self
+            .inner
+            .save_content(note, content)
+            .await
+            .map_err(Self::context(Operation::SaveContent, note.location()))?;
+        note.set_metadata(Metadata::parse(&content.content));
 
         Ok(etag)
     }
+
+    async fn list_notes(
+        &self,
+        path: &CollectionPath,
+    ) -> Result<Vec<(Box<dyn AnyNote>, CollectionPath)>, Error> {
+        self.inner
+            .list_notes(path)
+            .await
+            .map_err(Self::context(Operation::ListItems, path.last().location()))
+    }
+
+    async fn search(&self, query: &str, scope: &CollectionPath) -> Result<Vec<SearchMatch>, Error> {
+        self.inner
+            .search(query, scope)
+            .await
+            .map_err(Self::context(Operation::Search, scope.last().location()))
+    }
+
+    fn watch_collection(
+        &self,
+        collection: &dyn AnyCollection,
+        on_event: Box<dyn Fn(ItemEvent)>,
+    ) -> Result<Watch, Error> {
+        let collection = Collection::<S>::from_any(collection).unwrap();
+
+        self.inner
+            .watch_collection(collection, on_event)
+            .map_err(Self::context(Operation::Watch, collection.location()))
+    }
+
+    fn watch_note(
+        &self,
+        note: &dyn AnyNote,
+        on_event: Box<dyn Fn(ItemEvent)>,
+    ) -> Result<Watch, Error> {
+        let note = Note::<S>::from_any(note).unwrap();
+
+        self.inner
+            .watch_note(note, on_event)
+            .map_err(Self::context(Operation::Watch, note.location()))
+    }
 }
@@ -0,0 +1,176 @@
+use std::ops::Range;
+
+use super::frontmatter::{self, Frontmatter, Metadata, Value};
+
+/// Returns the tags of a note: those listed in its `metadata`, followed by
+/// `#tags` in its text. Each tag is only given once.
+pub fn note_tags(metadata: &Metadata, content: &str) -> Vec<String> {
+    let (_, body) = split(content);
+
+    let mut tags = metadata.tags.clone();
+    for range in inline_tags(body) {
+        let tag = &body[range];
+        if !tags.iter().any(|known| known == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+
+    tags
+}
+
+/// Returns `content` with the tag `from` renamed to `to`, or `None` if the
+/// note doesn't have that tag. Renaming to a tag the note already has merges
+/// both.
+pub fn rename_tag(content: &str, from: &str, to: &str) -> Option<String> {
+    let (frontmatter, body) = split(content);
+    let head = &content[..content.len() - body.len()];
+
+    let mut renamed = head.to_string();
+    let mut rest = 0;
+    for range in inline_tags(body)
+        .into_iter()
+        .filter(|range| &body[range.clone()] == from)
+    {
+        renamed.push_str(&body[rest..range.start]);
+        renamed.push_str(to);
+        rest = range.end;
+    }
+    // every tag follows a `#`, so nothing was renamed if this is still zero
+    let renamed_inline = rest > 0;
+    renamed.push_str(&body[rest..]);
+
+    let listed_tags = frontmatter
+        .map(|frontmatter| frontmatter.metadata.tags)
+        .filter(|tags| tags.iter().any(|tag| tag == from));
+    if let Some(listed_tags) = &listed_tags {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in listed_tags {
+            let tag = if tag == from { to } else { tag };
+            if !tags.iter().any(|known| known == tag) {
+                tags.push(tag.to_string());
+            }
+        }
+        renamed = frontmatter::set_field(&renamed, "tags", Some(&Value::List(tags)));
+    }
+
+    (renamed_inline || listed_tags.is_some()).then_some(renamed)
+}
+
+fn split(content: &str) -> (Option<Frontmatter>, &str) {
+    match Frontmatter::parse(content) {
+        Some(frontmatter) => {
+            let body = frontmatter.body(content);
+            (Some(frontmatter), body)
+        }
+        None => (None, content),
+    }
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '-' | '/')
+}
+
+/// Finds `#tags` outside of code, returning where their names are.
+fn inline_tags(text: &str) -> Vec<Range<usize>> {
+    let mut tags = Vec::new();
+    let mut offset = 0;
+    let mut in_code_block = false;
+
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_code_block = !in_code_block;
+            continue;
+        }
+        if in_code_block {
+            continue;
+        }
+
+        let mut in_code_span = false;
+        let mut previous = None;
+        let mut chars = line.char_indices().peekable();
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '`' => in_code_span = !in_code_span,
+                '#' if !in_code_span
+                    && previous.is_none_or(|p: char| p.is_whitespace() || "([".contains(p)) =>
+                {
+                    let name_start = index + 1;
+                    let mut name_end = name_start;
+                    while let Some((index, c)) = chars.next_if(|(_, c)| is_tag_char(*c)) {
+                        name_end = index + c.len_utf8();
+                    }
+                    let name = &line[name_start..name_end];
+
+                    // headings and things like `#1` aren't tags
+                    if name.chars().any(|c| !c.is_ascii_digit()) {
+                        tags.push(start + name_start..start + name_end);
+                    }
+                    previous = line[..name_end].chars().last();
+                    continue;
+                }
+                _ => {}
+            }
+            previous = Some(c);
+        }
+    }
+
+    tags
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOTE: &str = "---
+tags: [rust, gtk]
+---
+# Heading #1
+
+Some #notes about #rust/gtk and #rust, see issue #12 or page#anchor.
+
+```
+#not-a-tag
+```
+Also `#code` but (#last).
+";
+
+    #[test]
+    fn test_note_tags() {
+        assert_eq!(
+            note_tags(&Metadata::parse(NOTE), NOTE),
+            vec!["rust", "gtk", "notes", "rust/gtk", "last"]
+        );
+        assert_eq!(
+            note_tags(&Metadata::default(), "#only inline"),
+            vec!["only"]
+        );
+    }
+
+    #[test]
+    fn test_rename_tag() {
+        assert_eq!(
+            rename_tag(NOTE, "rust", "Rust"),
+            Some(
+                NOTE.replace("[rust, gtk]", "[Rust, gtk]")
+                    .replace("and #rust,", "and #Rust,")
+            )
+        );
+        assert_eq!(
+            rename_tag(NOTE, "notes", "note"),
+            Some(NOTE.replace("Some #notes", "Some #note"))
+        );
+        assert_eq!(rename_tag(NOTE, "code", "other"), None);
+    }
+
+    #[test]
+    fn test_merge_tags() {
+        assert_eq!(
+            rename_tag(NOTE, "gtk", "rust"),
+            Some(NOTE.replace("[rust, gtk]", "[rust]"))
+        );
+    }
+}
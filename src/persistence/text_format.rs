@@ -0,0 +1,217 @@
+use crate::errors::Error;
+
+/// Character encodings notes are read and written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    /// UTF-8 starting with a byte order mark
+    Utf8Bom,
+    Utf16Le,
+    Utf16Be,
+    /// a superset of ISO-8859-1, for text that isn't valid UTF-8
+    Windows1252,
+}
+
+impl Encoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 | Encoding::Utf8Bom => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    Crlf,
+}
+
+/// How the content of a note is stored, so that saving it keeps its
+/// encoding and line endings. Notes are edited as UTF-8 with `\n` line
+/// endings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TextFormat {
+    pub encoding: Encoding,
+    pub line_ending: LineEnding,
+}
+
+const UTF8_BOM: &[u8] = &[0xef, 0xbb, 0xbf];
+const UTF16LE_BOM: &[u8] = &[0xff, 0xfe];
+const UTF16BE_BOM: &[u8] = &[0xfe, 0xff];
+
+/// Characters of windows-1252 at bytes 0x80 to 0x9f, where ISO-8859-1 has
+/// control characters. Unassigned bytes keep the control characters, so that
+/// any byte can be read and written back.
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+impl TextFormat {
+    /// Decodes the content of a note. Text with a byte order mark is read
+    /// accordingly, other text as UTF-8 if possible and as windows-1252
+    /// otherwise. That also reads ISO-8859-1 text, and lets characters like
+    /// € be added to it later.
+    pub fn decode(bytes: &[u8]) -> Result<(String, TextFormat), Error> {
+        let (text, encoding) = if let Some(rest) = bytes.strip_prefix(UTF8_BOM) {
+            (decode_utf8(rest)?, Encoding::Utf8Bom)
+        } else if let Some(rest) = bytes.strip_prefix(UTF16LE_BOM) {
+            (decode_utf16(rest, u16::from_le_bytes)?, Encoding::Utf16Le)
+        } else if let Some(rest) = bytes.strip_prefix(UTF16BE_BOM) {
+            (decode_utf16(rest, u16::from_be_bytes)?, Encoding::Utf16Be)
+        } else if let Ok(text) = std::str::from_utf8(bytes) {
+            (text.to_string(), Encoding::Utf8)
+        } else {
+            (decode_windows_1252(bytes), Encoding::Windows1252)
+        };
+
+        // only convert line endings that are used throughout
+        let crlf_count = text.matches("\r\n").count();
+        let (text, line_ending) = if crlf_count > 0 && crlf_count == text.matches('\n').count() {
+            (text.replace("\r\n", "\n"), LineEnding::Crlf)
+        } else {
+            (text, LineEnding::Lf)
+        };
+
+        Ok((
+            text,
+            TextFormat {
+                encoding,
+                line_ending,
+            },
+        ))
+    }
+
+    /// Encodes `text` the way the note was stored. Fails for characters the
+    /// encoding can't represent.
+    pub fn encode(&self, text: &str) -> Result<Vec<u8>, Error> {
+        let text = match self.line_ending {
+            LineEnding::Lf => text.to_string(),
+            LineEnding::Crlf => text.replace('\n', "\r\n"),
+        };
+
+        let bytes = match self.encoding {
+            Encoding::Utf8 => text.into_bytes(),
+            Encoding::Utf8Bom => [UTF8_BOM, text.as_bytes()].concat(),
+            Encoding::Utf16Le => UTF16LE_BOM
+                .iter()
+                .copied()
+                .chain(text.encode_utf16().flat_map(u16::to_le_bytes))
+                .collect(),
+            Encoding::Utf16Be => UTF16BE_BOM
+                .iter()
+                .copied()
+                .chain(text.encode_utf16().flat_map(u16::to_be_bytes))
+                .collect(),
+            Encoding::Windows1252 => text
+                .chars()
+                .map(
+                    |c| match WINDOWS_1252.iter().position(|known| *known == c) {
+                        Some(index) => Ok(0x80 + index as u8),
+                        None if (c as u32) < 0x80 || (0xa0..0x100).contains(&(c as u32)) => {
+                            Ok(c as u8)
+                        }
+                        None => Err(self.encode_error(c)),
+                    },
+                )
+                .collect::<Result<_, _>>()?,
+        };
+
+        Ok(bytes)
+    }
+
+    fn encode_error(&self, c: char) -> Error {
+        Error::EncodeError(format!(
+            "“{}” cannot be saved as {}",
+            c,
+            self.encoding.name()
+        ))
+    }
+}
+
+fn decode_utf8(bytes: &[u8]) -> Result<String, Error> {
+    Ok(String::from_utf8(bytes.to_vec())?)
+}
+
+fn decode_utf16(bytes: &[u8], to_unit: fn([u8; 2]) -> u16) -> Result<String, Error> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(Error::DecodeError(String::from(
+            "UTF-16 text has an odd number of bytes",
+        )));
+    }
+
+    let units = bytes
+        .chunks_exact(2)
+        .map(|pair| to_unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .map_err(|err| Error::DecodeError(err.to_string()))
+}
+
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|byte| match byte {
+            0x80..0xa0 => WINDOWS_1252[(byte - 0x80) as usize],
+            _ => *byte as char,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(bytes: &[u8]) -> (String, TextFormat) {
+        let (text, format) = TextFormat::decode(bytes).unwrap();
+        assert_eq!(format.encode(&text).unwrap(), bytes);
+
+        (text, format)
+    }
+
+    #[test]
+    fn test_utf8() {
+        let (text, format) = round_trip("# Café\r\nTschüss\r\n".as_bytes());
+        assert_eq!(text, "# Café\nTschüss\n");
+        assert_eq!(format.encoding, Encoding::Utf8);
+        assert_eq!(format.line_ending, LineEnding::Crlf);
+
+        let (text, format) = round_trip(b"\xef\xbb\xbfmixed\r\nline\nendings\r");
+        assert_eq!(text, "mixed\r\nline\nendings\r");
+        assert_eq!(format.encoding, Encoding::Utf8Bom);
+        assert_eq!(format.line_ending, LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_utf16() {
+        let (text, format) = round_trip(b"\xff\xfeh\x00\xe9\x00\n\x00=\xd8\x00\xde");
+        assert_eq!(text, "hé\n😀");
+        assert_eq!(format.encoding, Encoding::Utf16Le);
+
+        let (text, format) = round_trip(b"\xfe\xff\x00h\x00\xe9");
+        assert_eq!(text, "hé");
+        assert_eq!(format.encoding, Encoding::Utf16Be);
+
+        assert!(TextFormat::decode(b"\xff\xfeh").is_err());
+    }
+
+    #[test]
+    fn test_single_byte() {
+        let (text, format) = round_trip(b"caf\xe9 \x80 \x93quoted\x94");
+        assert_eq!(text, "café € “quoted”");
+        assert_eq!(format.encoding, Encoding::Windows1252);
+
+        // plain ISO-8859-1 text can take characters only windows-1252 has
+        let (text, format) = round_trip(b"caf\xe9 \x81");
+        assert_eq!(text, "café \u{81}");
+        assert_eq!(format.encoding, Encoding::Windows1252);
+        assert_eq!(format.encode("5 €").unwrap(), b"5 \x80");
+
+        assert!(format.encode("5 ₿").is_err());
+    }
+}
@@ -0,0 +1,159 @@
+use std::{env, path::PathBuf, time::Duration};
+
+use gtk::{gio, prelude::*};
+
+use crate::errors::Error;
+use crate::persistence::file_rules::FileRules;
+use crate::util::markdown::MarkdownOptions;
+
+const DEFAULT_NOTES_ROOTDIR: &str = "./sample-notes";
+const DEFAULT_AUTOSAVE_DELAY: Duration = Duration::from_millis(1000);
+
+/// File in the root folder of a notebook holding its settings.
+pub const NOTEBOOK_SETTINGS_FILE: &str = ".notes.toml";
+
+/// Settings given on startup, read from environment variables:
+///
+/// - `NOTES_ROOTDIR`: folder containing the notebook
+/// - `NOTES_AUTOSAVE_DELAY`: milliseconds to wait after the last change
+///   before saving a note
+/// - `NOTES_NOTE_EXTENSIONS`: comma-separated extensions of files shown as
+///   notes, e.g. `md,txt,org`
+/// - `NOTES_IGNORE`: comma-separated name patterns of files and folders to
+///   leave out, e.g. `.*,*.swp`; an empty value shows everything
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub storage_uri: String,
+    pub autosave_delay: Duration,
+    pub file_rules: FileRules,
+}
+
+impl Settings {
+    pub fn from_env() -> Self {
+        // a missing folder is reported by the app, which then asks for another one
+        let notes_rootdir =
+            PathBuf::from(env::var_os("NOTES_ROOTDIR").unwrap_or(DEFAULT_NOTES_ROOTDIR.into()));
+        let notes_rootdir = notes_rootdir.canonicalize().unwrap_or(notes_rootdir);
+
+        let autosave_delay = env::var("NOTES_AUTOSAVE_DELAY")
+            .ok()
+            .and_then(|delay| delay.parse().ok())
+            .map_or(DEFAULT_AUTOSAVE_DELAY, Duration::from_millis);
+
+        let mut file_rules = FileRules::default();
+        if let Ok(extensions) = env::var("NOTES_NOTE_EXTENSIONS") {
+            file_rules.note_extensions = split_list(&extensions)
+                .map(|extension| extension.trim_start_matches('.').to_string())
+                .collect();
+        }
+        if let Ok(patterns) = env::var("NOTES_IGNORE") {
+            file_rules.ignore_patterns = split_list(&patterns).map(String::from).collect();
+        }
+
+        Self {
+            storage_uri: gio::File::for_path(&notes_rootdir).uri().to_string(),
+            autosave_delay,
+            file_rules,
+        }
+    }
+}
+
+fn split_list(list: &str) -> impl Iterator<Item = &str> {
+    list.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+/// Settings of a notebook, read from [`NOTEBOOK_SETTINGS_FILE`] in its root
+/// folder, e.g.
+///
+/// ```toml
+/// [markdown]
+/// gfm = true
+/// frontmatter = true
+/// math = true
+/// ```
+///
+/// Missing settings keep their defaults.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NotebookSettings {
+    pub markdown: MarkdownOptions,
+}
+
+impl NotebookSettings {
+    /// Reads the settings of the notebook at `root_location`, falling back to
+    /// the defaults if there are none.
+    pub async fn load(root_location: &str) -> Result<Self, Error> {
+        let file = gio::File::for_uri(root_location).child(NOTEBOOK_SETTINGS_FILE);
+
+        match file.load_contents_future().await {
+            Ok((bytes, _)) => Ok(Self::parse(&String::from_utf8_lossy(&bytes))),
+            Err(err) if err.matches(gio::IOErrorEnum::NotFound) => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// Parses the `key = value` lines of the settings file, ignoring
+    /// anything not understood.
+    fn parse(text: &str) -> Self {
+        let mut settings = Self::default();
+        let mut section = "";
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(name) = line
+                .strip_prefix('[')
+                .and_then(|line| line.strip_suffix(']'))
+            {
+                section = name.trim();
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.split('#').next().unwrap_or_default().trim();
+            let Ok(enabled) = value.parse::<bool>() else {
+                continue;
+            };
+
+            match (section, key.trim()) {
+                ("markdown", "gfm") => settings.markdown.gfm = enabled,
+                ("markdown", "frontmatter") => settings.markdown.frontmatter = enabled,
+                ("markdown", "math") => settings.markdown.math = enabled,
+                _ => {}
+            }
+        }
+
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notebook_settings() {
+        let settings = NotebookSettings::parse(
+            "# notebook settings\n\
+             gfm = false\n\
+             [markdown]\n\
+             gfm = false # plain CommonMark\n\
+             math = true\n\
+             frontmatter = maybe\n\
+             [other]\n\
+             frontmatter = false\n",
+        );
+
+        assert_eq!(
+            settings.markdown,
+            MarkdownOptions {
+                gfm: false,
+                frontmatter: true,
+                math: true,
+            }
+        );
+    }
+}
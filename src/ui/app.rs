@@ -1,85 +1,744 @@
+use std::cell::RefCell;
 use std::convert::identity;
+use std::rc::Rc;
 
+use crate::LOG_DOMAIN;
+use crate::errors::{Error, Operation};
 use crate::icon_names;
 use crate::persistence::build_storage_from_url;
-use crate::persistence::models::{AnyItem, AnyNote, CollectionPath, ItemKind};
-use crate::persistence::storage::{ItemStorage, NoteContent};
+use crate::persistence::file_rules::FileRules;
+use crate::persistence::index::SearchIndex;
+use crate::persistence::models::{
+    AnyAttachment, AnyCollection, AnyItem, AnyNote, CollectionPath, ItemKind,
+};
+use crate::persistence::storage::{self, ItemEvent, ItemStorage, NoteContent, SearchMatch, Watch};
+use crate::persistence::tags;
+use crate::persistence::text_format::TextFormat;
+use crate::settings::{NotebookSettings, Settings};
 use crate::ui::note_view::{NoteView, NoteViewMsg};
 use crate::ui::sidebar::Sidebar;
 use crate::ui::title::Title;
+use crate::util::diff::merge_with_conflict_markers;
+use crate::util::wiki::{self, WikiTargets};
 use adw;
-use gtk::{UriLauncher, Window, prelude::*};
+use gtk::{UriLauncher, Window, gio, glib, prelude::*};
 use relm4::actions::{AccelsPlus, RelmAction, RelmActionGroup};
 use relm4::{main_application, prelude::*};
 
 use super::about_dialog::{AboutDialog, AboutDialogMsg};
-use super::note_view::Mode;
+use super::attachment_view::Preview;
+use super::autosave::Autosave;
+use super::dialogs::{
+    ConflictResolution, choose_notes_folder, confirm_destructive, prompt_for_name, resolve_conflict,
+};
+use super::note_resources;
+use super::note_view::{Highlight, Mode};
 use super::sidebar::SidebarMsg;
-use super::title::{TitleMode, TitleMsg};
+use super::title::{SaveState, TitleMode, TitleMsg};
 
 relm4::new_action_group!(pub AppActions, "app");
 relm4::new_stateless_action!(pub AboutAction, AppActions, "about");
 relm4::new_stateless_action!(pub FocusNoteListAction, AppActions, "focus-note-list");
 relm4::new_stateless_action!(pub FocusSearchEntryAction, AppActions, "focus-search-entry");
+relm4::new_stateless_action!(pub NewCollectionAction, AppActions, "new-collection");
+relm4::new_stateless_action!(pub NewNoteAction, AppActions, "new-note");
 relm4::new_stateless_action!(pub QuitAction, AppActions, "quit");
 relm4::new_stateless_action!(pub ToggleModeAction, AppActions, "toggle");
 relm4::new_stateless_action!(pub UpAction, AppActions, "up");
 
+const NEW_NOTE_NAME: &str = "New note";
+const NEW_NOTE_EXTENSION: &str = "md";
+
+#[derive(Debug)]
+pub enum ClipboardContent {
+    Cut(Box<dyn AnyItem>),
+    Copied(Box<dyn AnyItem>),
+}
+
 pub struct App {
     about_dialog_controller: Controller<AboutDialog>,
-    storage: Box<dyn ItemStorage>,
+    storage: Rc<dyn ItemStorage>,
+    index: Rc<SearchIndex>,
     sidebar: AsyncController<Sidebar>,
     note_view: AsyncController<NoteView>,
     title_controller: Controller<Title>,
+    toast_overlay: adw::ToastOverlay,
+    banner: adw::Banner,
     current_path: CollectionPath,
     current_note: Option<Box<dyn AnyNote>>,
+    current_attachment: Option<Box<dyn AnyAttachment>>,
+    /// etag of the current note as last loaded or saved by us
+    current_etag: Option<String>,
+    /// encoding and line endings the current note is saved with
+    current_format: TextFormat,
+    autosave: Autosave,
+    /// watches the collection shown in the sidebar, by its location
+    collection_watch: Option<(String, Watch)>,
+    /// watches the current note
+    note_watch: Option<Watch>,
+    clipboard: Option<ClipboardContent>,
     mode: Mode,
+    /// tells which names are notes, e.g. for notes created from wiki links
+    file_rules: FileRules,
 }
 
 impl App {
-    async fn update_note_list(&self, collection_path: &CollectionPath) {
-        let notes = self
+    async fn update_note_list(&mut self, sender: &AsyncComponentSender<Self>) {
+        match self.storage.as_ref().list_items(&self.current_path).await {
+            Ok(notes) => {
+                self.banner.set_revealed(false);
+                self.index.mark_stale();
+                self.watch_current_collection(sender);
+                self.sidebar
+                    .sender()
+                    .emit(SidebarMsg::UpdateNoteList(notes));
+            }
+            Err(error) => sender.input(AppMsg::ShowError {
+                error,
+                retry: Some(Box::new(AppMsg::UpdateItemList())),
+            }),
+        }
+    }
+
+    fn watch_current_collection(&mut self, sender: &AsyncComponentSender<Self>) {
+        let collection = self.current_path.last();
+        if self
+            .collection_watch
+            .as_ref()
+            .is_some_and(|(location, _)| *location == collection.location())
+        {
+            return;
+        }
+
+        let sender_clone = sender.clone();
+        let result = self.storage.as_ref().watch_collection(
+            collection.as_ref(),
+            Box::new(move |event| sender_clone.input(AppMsg::CollectionChanged(event))),
+        );
+        self.collection_watch = match result {
+            Ok(watch) => Some((collection.location(), watch)),
+            Err(error) => {
+                sender.input(AppMsg::ShowError { error, retry: None });
+                None
+            }
+        };
+    }
+
+    fn watch_current_note(&mut self, sender: &AsyncComponentSender<Self>) {
+        let Some(note) = self.current_note.as_ref() else {
+            self.note_watch = None;
+            return;
+        };
+
+        let sender_clone = sender.clone();
+        let result = self.storage.as_ref().watch_note(
+            note.as_ref(),
+            Box::new(move |event| sender_clone.input(AppMsg::NoteChanged(event))),
+        );
+        self.note_watch = match result {
+            Ok(watch) => Some(watch),
+            Err(error) => {
+                sender.input(AppMsg::ShowError { error, retry: None });
+                None
+            }
+        };
+    }
+
+    /// Picks up changes made to the current note elsewhere. Local edits that
+    /// aren't saved yet are saved right away instead, which makes the user
+    /// resolve the conflict.
+    async fn reload_note(&mut self, sender: &AsyncComponentSender<Self>) {
+        let Some(note) = self.current_note.clone() else {
+            return;
+        };
+        if self.autosave.has_conflict() {
+            return;
+        }
+        if let Some((note, content)) = self.autosave.flush() {
+            let etag = self.current_etag.clone();
+            self.save_note(note, content, etag, sender).await;
+            return;
+        }
+
+        match self.storage.as_ref().load_content(note.as_ref()).await {
+            // most likely caused by our own save
+            Ok(content) if content.etag == self.current_etag => {}
+            Ok(content) => {
+                self.current_etag = content.etag;
+                self.current_format = content.format;
+                self.note_view
+                    .emit(NoteViewMsg::ReloadedContent(content.content));
+            }
+            Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+        }
+    }
+
+    /// Brings the index up to date with a single change in the background,
+    /// telling the rest of the app if anything changed.
+    fn update_index_for(&self, event: ItemEvent, sender: &AsyncComponentSender<Self>) {
+        let index = self.index.clone();
+        let storage = self.storage.clone();
+        let sender = sender.clone();
+        relm4::spawn_local(async move {
+            match index.apply_event(storage.as_ref(), &event).await {
+                Ok(true) => sender.input(AppMsg::IndexUpdated()),
+                Ok(false) => {}
+                Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+            }
+        });
+    }
+
+    /// Renames the tag `from` to `to` in all notes having it. Notes that
+    /// already have `to` end up with it only once.
+    async fn rename_tag(&mut self, from: &str, to: &str, sender: &AsyncComponentSender<Self>) {
+        self.flush_autosave(sender).await;
+
+        let tagged = match self.index.notes_with_tag(self.storage.as_ref(), from).await {
+            Ok(tagged) => tagged,
+            Err(error) => {
+                sender.input(AppMsg::ShowError { error, retry: None });
+                return;
+            }
+        };
+
+        let mut renamed_count = 0;
+        for search_match in tagged {
+            let note = search_match.note;
+            let result = match self.storage.as_ref().load_content(note.as_ref()).await {
+                Ok(content) => match tags::rename_tag(&content.content, from, to) {
+                    Some(renamed) => self
+                        .storage
+                        .as_ref()
+                        .save_content(
+                            note.as_ref(),
+                            &NoteContent {
+                                content: renamed,
+                                etag: content.etag,
+                                format: content.format,
+                            },
+                        )
+                        .await
+                        .map(|_| true),
+                    None => Ok(false),
+                },
+                Err(error) => Err(error),
+            };
+
+            match result {
+                Ok(true) => renamed_count += 1,
+                Ok(false) => {}
+                Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+            }
+        }
+
+        self.reload_note(sender).await;
+        self.index.mark_stale();
+        sender.input(AppMsg::UpdateIndex());
+
+        let toast = adw::Toast::builder()
+            .title(glib::markup_escape_text(&format!(
+                "Renamed #{} to #{} in {} notes",
+                from, to, renamed_count
+            )))
+            .build();
+        self.toast_overlay.add_toast(toast);
+    }
+
+    /// Shows a note. Unsaved changes to the current note are saved first, the
+    /// note stays open if that fails.
+    async fn open_note(&mut self, note: Box<dyn AnyNote>, sender: &AsyncComponentSender<Self>) {
+        if !self.flush_autosave(sender).await {
+            return;
+        }
+
+        match self.storage.as_ref().load_content(&*note).await {
+            Ok(content) => {
+                self.current_note = Some(note.clone());
+                self.current_attachment = None;
+                self.current_etag = content.etag;
+                self.current_format = content.format;
+                self.watch_current_note(sender);
+                self.note_view.emit(NoteViewMsg::LoadedNote {
+                    note,
+                    content: content.content,
+                });
+                self.title_controller
+                    .emit(TitleMsg::SetCurrentNote(self.current_note.clone()));
+                self.title_controller
+                    .emit(TitleMsg::SetSaveState(SaveState::Saved));
+                sender.input(AppMsg::UpdateBacklinks());
+            }
+            Err(error) => sender.input(AppMsg::ShowError {
+                error,
+                retry: Some(Box::new(AppMsg::SelectedItem(note.clone_box()))),
+            }),
+        }
+    }
+
+    /// Shows an attachment in place of the current note, provided unsaved
+    /// changes to the note could be saved.
+    async fn open_attachment(
+        &mut self,
+        attachment: Box<dyn AnyAttachment>,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        if !self.flush_autosave(sender).await {
+            return;
+        }
+
+        let result = match self.storage.as_ref().attachment_info(&*attachment).await {
+            Ok(info) => Preview::load(self.storage.as_ref(), &*attachment, &info)
+                .await
+                .map(|preview| (info, preview)),
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok((info, preview)) => {
+                self.current_note = None;
+                self.current_etag = None;
+                self.note_watch = None;
+                self.current_attachment = Some(attachment.clone());
+                self.note_view.emit(NoteViewMsg::LoadedAttachment {
+                    attachment,
+                    info,
+                    preview,
+                });
+                self.title_controller.emit(TitleMsg::SetCurrentNote(None));
+            }
+            Err(error) => sender.input(AppMsg::ShowError {
+                error,
+                retry: Some(Box::new(AppMsg::SelectedItem(attachment.clone_box()))),
+            }),
+        }
+    }
+
+    /// Saves changes to the current note that are waiting for the autosave
+    /// delay. Returns whether everything is saved now.
+    async fn flush_autosave(&mut self, sender: &AsyncComponentSender<Self>) -> bool {
+        match self.autosave.flush() {
+            Some((note, content)) => {
+                let etag = self.current_etag.clone();
+                self.save_note(note, content, etag, sender).await
+            }
+            None => !self.autosave.has_conflict(),
+        }
+    }
+
+    /// Saves the content of `note`, provided it hasn't been changed elsewhere
+    /// since `etag`. Pass no etag to overwrite any changes. Returns whether
+    /// saving succeeded.
+    async fn save_note(
+        &mut self,
+        note: Box<dyn AnyNote>,
+        content: String,
+        etag: Option<String>,
+        sender: &AsyncComponentSender<Self>,
+    ) -> bool {
+        let is_current_note = self
+            .current_note
+            .as_ref()
+            .is_some_and(|current_note| current_note.location() == note.location());
+
+        self.title_controller
+            .emit(TitleMsg::SetSaveState(SaveState::Saving));
+        let result = self
             .storage
             .as_ref()
-            .list_items(collection_path)
-            .await
-            .map_or_else(
-                |err| {
-                    panic!("error loading note list {:?}", err.to_string());
-                    // self.model.error = Some(err.to_string());
+            .save_content(
+                note.as_ref(),
+                &NoteContent {
+                    content: content.clone(),
+                    etag,
+                    format: self.current_format,
                 },
-                Some,
             )
-            .unwrap();
+            .await;
+        let saved = result.is_ok();
+        match result {
+            Ok(etag) => {
+                if is_current_note {
+                    self.current_etag = Some(etag);
+                }
+                // the index picks up the change from the note's watch
+                self.title_controller
+                    .emit(TitleMsg::SetSaveState(SaveState::Saved));
+            }
+            Err(err) if matches!(err.cause(), Error::Conflict { .. }) => {
+                self.autosave.conflicted(note.clone(), content);
+                self.title_controller
+                    .emit(TitleMsg::SetSaveState(SaveState::Unsaved));
+                sender.input(AppMsg::StartResolveConflict(note));
+            }
+            Err(error) => {
+                self.title_controller
+                    .emit(TitleMsg::SetSaveState(SaveState::Unsaved));
+                sender.input(AppMsg::ShowError {
+                    error,
+                    retry: Some(Box::new(AppMsg::ContentChanged { note, content })),
+                });
+            }
+        }
+
+        saved
+    }
+
+    /// Listing failures leave the sidebar stale, so they stay visible in a
+    /// banner until a retry succeeds. Everything else is shown as a toast.
+    fn show_error(&self, error: Error, retry: Option<AppMsg>, sender: &AsyncComponentSender<Self>) {
+        glib::g_warning!(LOG_DOMAIN, "{}", error);
+
+        if error.operation() == Some(Operation::ListItems) {
+            self.banner
+                .set_title(&glib::markup_escape_text(&error.summary()));
+            self.banner.set_revealed(true);
+            return;
+        }
+
+        let toast = adw::Toast::builder()
+            .title(glib::markup_escape_text(&error.summary()))
+            .priority(adw::ToastPriority::High)
+            .build();
+        if let Some(retry) = retry {
+            toast.set_button_label(Some("Retry"));
+            let retry = RefCell::new(Some(retry));
+            let sender = sender.clone();
+            toast.connect_button_clicked(move |_| {
+                if let Some(retry) = retry.borrow_mut().take() {
+                    sender.input(retry);
+                }
+            });
+        }
+        self.toast_overlay.add_toast(toast);
+    }
+
+    /// Creates an empty note in the current collection, picking the first
+    /// "New note.md", "New note 2.md", … name that is not taken yet.
+    async fn create_new_note(&self) -> Result<Box<dyn AnyNote>, Error> {
+        let collection = self.current_path.last();
+
+        for i in 1.. {
+            let name = if i == 1 {
+                format!("{}.{}", NEW_NOTE_NAME, NEW_NOTE_EXTENSION)
+            } else {
+                format!("{} {}.{}", NEW_NOTE_NAME, i, NEW_NOTE_EXTENSION)
+            };
+
+            match self
+                .storage
+                .as_ref()
+                .create_note(collection.as_ref(), &name, "")
+                .await
+            {
+                Err(err) if matches!(err.cause(), Error::AlreadyExists { .. }) => continue,
+                result => return result,
+            }
+        }
+
+        unreachable!()
+    }
+
+    /// Looks up the item at `location` by walking down from the root
+    /// collection. Returns it along with the collections containing it.
+    async fn find_item(
+        &self,
+        location: &str,
+    ) -> Result<Option<(CollectionPath, Box<dyn AnyItem>)>, Error> {
+        let names = match note_resources::resource_names(location) {
+            Some(names) => names,
+            None => {
+                let relative_path = gio::File::for_uri(&self.storage.root().location())
+                    .relative_path(&gio::File::for_uri(location));
+                let Some(relative_path) = relative_path else {
+                    return Ok(None);
+                };
+                relative_path
+                    .iter()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .collect()
+            }
+        };
+
+        storage::find_item(self.storage.as_ref(), &names).await
+    }
+
+    /// Creates the missing note a wiki link points to and opens it for
+    /// editing. Targets like `[[Plan]]` end up in the current collection,
+    /// ones like `[[projects/Plan]]` in the collections named from the root,
+    /// which are created if they don't exist yet.
+    async fn create_linked_note(&mut self, target: &str, sender: &AsyncComponentSender<Self>) {
+        let target = target.split('#').next().unwrap_or_default();
+        let mut names: Vec<&str> = target.trim().trim_matches('/').split('/').collect();
+        let name = names.pop().unwrap_or_default().trim();
+        if name.is_empty() || names.iter().any(|name| name.trim().is_empty()) {
+            return;
+        }
+        let name = if self.file_rules.has_note_extension(name) {
+            name.to_string()
+        } else {
+            format!("{}.{}", name, NEW_NOTE_EXTENSION)
+        };
+
+        let collection = if names.is_empty() {
+            Ok(self.current_path.last().clone())
+        } else {
+            self.make_collections(&names).await
+        };
+        let result = match collection {
+            Ok(collection) => {
+                self.storage
+                    .as_ref()
+                    .create_note(collection.as_ref(), &name, "")
+                    .await
+            }
+            Err(error) => Err(error),
+        };
+        match result {
+            Ok(note) => {
+                self.update_note_list(sender).await;
+                self.open_note(note, sender).await;
+                sender.input(AppMsg::SetMode(Mode::Edit));
+                sender.input(AppMsg::UpdateIndex());
+            }
+            Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+        }
+    }
+
+    /// Looks up the collections `names` below the root, one inside the
+    /// other, creating the ones that don't exist yet. Returns the last one.
+    async fn make_collections(&self, names: &[&str]) -> Result<Box<dyn AnyCollection>, Error> {
+        let mut path = CollectionPath::from(self.storage.root());
+        for name in names.iter().map(|name| name.trim()) {
+            let existing = self
+                .storage
+                .as_ref()
+                .list_items(&path)
+                .await?
+                .into_iter()
+                .find(|item| item.kind() == ItemKind::Collection && item.name() == name)
+                .and_then(|item| item.as_collection());
+            let collection = match existing {
+                Some(collection) => collection,
+                None => {
+                    self.storage
+                        .as_ref()
+                        .create_collection(path.last().as_ref(), name)
+                        .await?
+                }
+            };
+            path.push(collection);
+        }
+
+        Ok(path.last().clone())
+    }
+
+    fn close_note_if_inside(&mut self, location: &str) {
+        let is_inside = |item_location: String| {
+            item_location == location || item_location.starts_with(&format!("{}/", location))
+        };
+        let inside_deleted_item = self
+            .current_note
+            .as_ref()
+            .is_some_and(|note| is_inside(note.location()))
+            || self
+                .current_attachment
+                .as_ref()
+                .is_some_and(|attachment| is_inside(attachment.location()));
+
+        if inside_deleted_item {
+            self.autosave.flush();
+            self.current_note = None;
+            self.current_attachment = None;
+            self.current_etag = None;
+            self.note_watch = None;
+            self.note_view.emit(NoteViewMsg::UnloadedNote());
+            self.title_controller.emit(TitleMsg::SetCurrentNote(None));
+        }
+    }
+
+    /// Follows the current note to its new location after it or one of the
+    /// collections containing it was renamed or moved.
+    async fn item_relocated(
+        &mut self,
+        item: &dyn AnyItem,
+        relocated: Box<dyn AnyItem>,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        // the current note's location with the moved item's part replaced
+        let new_location = self.current_note.as_ref().and_then(|note| {
+            let location = note.location();
+            if location == item.location() {
+                return Some(relocated.location());
+            }
+            location
+                .strip_prefix(&format!("{}/", item.location()))
+                .map(|rest| format!("{}/{}", relocated.location(), rest))
+        });
+        let relocated_note = match new_location {
+            Some(location) if location == relocated.location() => relocated.as_note(),
+            Some(location) => match self.find_item(&location).await {
+                Ok(Some((_, item))) => item.as_note(),
+                _ => None,
+            },
+            None => None,
+        };
 
-        self.sidebar
-            .sender()
-            .emit(SidebarMsg::UpdateNoteList(notes));
+        if let Some(note) = relocated_note {
+            self.current_note = Some(note.clone());
+            // unsaved changes were meant for the old location
+            if let Some((_, content)) = self.autosave.flush() {
+                let etag = self.current_etag.clone();
+                self.save_note(note.clone(), content, etag, sender).await;
+            }
+            self.watch_current_note(sender);
+            self.note_view.emit(NoteViewMsg::RelocatedNote(note));
+            self.title_controller
+                .emit(TitleMsg::SetCurrentNote(self.current_note.clone()));
+        } else {
+            self.close_note_if_inside(&item.location());
+        }
+    }
+
+    /// Keeps the current path valid after one of its collections got renamed
+    /// by looking up the collections below the renamed one again by name.
+    async fn collection_renamed(
+        &mut self,
+        collection: &dyn AnyCollection,
+        renamed: Box<dyn AnyCollection>,
+        sender: &AsyncComponentSender<Self>,
+    ) {
+        if let Some(index) = self.current_path.position(collection).filter(|i| *i > 0) {
+            let descendant_names: Vec<String> = self
+                .current_path
+                .iter()
+                .skip(index + 1)
+                .map(|c| c.name())
+                .collect();
+            let mut path = self.current_path.clone();
+            path.truncate(index);
+            path.push(renamed);
+
+            for name in descendant_names {
+                let Ok(items) = self.storage.as_ref().list_items(&path).await else {
+                    break;
+                };
+                let descendant = items
+                    .iter()
+                    .find(|item| item.kind() == ItemKind::Collection && item.name() == name)
+                    .and_then(|item| item.as_collection());
+                match descendant {
+                    Some(descendant) => path.push(descendant),
+                    None => break,
+                }
+            }
+
+            self.current_path = path;
+            self.sidebar
+                .emit(SidebarMsg::SetCollectionPath(self.current_path.clone()));
+        }
+
+        self.update_note_list(sender).await;
+    }
+}
+
+/// Keeps asking for a notes folder until one can be opened, or quits.
+async fn choose_storage(mut error: Error, file_rules: &FileRules) -> Box<dyn ItemStorage> {
+    loop {
+        let Some(uri) = choose_notes_folder(&error).await else {
+            main_application().quit();
+            // the app is shutting down, so it never gets to start up
+            return std::future::pending().await;
+        };
+
+        match build_storage_from_url(&uri, file_rules).await {
+            Ok(storage) => return storage,
+            Err(err) => error = err,
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum AppMsg {
+    AutosaveTimeout(u64),
     ClickedWebLink(String),
+    CollectionChanged(ItemEvent),
     ContentChanged {
         note: Box<dyn AnyNote>,
         content: String,
     },
+    CopyItem {
+        item: Box<dyn AnyItem>,
+        destination: Box<dyn AnyCollection>,
+    },
+    CreateCollection(String),
+    CreateNote(),
+    DeleteCollection {
+        collection: Box<dyn AnyCollection>,
+        recursive: bool,
+    },
+    DeleteItem {
+        item: Box<dyn AnyItem>,
+        permanently: bool,
+    },
+    IndexUpdated(),
+    EditorScrolled(usize),
+    /// tick or untick the task list item at the offset in the current note
+    ToggleTask(usize),
+    EditMetadata {
+        title: Option<String>,
+        tags: Vec<String>,
+    },
+    MoveItem {
+        item: Box<dyn AnyItem>,
+        destination: Box<dyn AnyCollection>,
+    },
+    NoteChanged(ItemEvent),
+    OpenSearchResult {
+        search_match: SearchMatch,
+        query: String,
+    },
     NoteContentChanged(String),
+    OpenLocalLink(String),
+    OpenWikiLink(String),
+    Paste(),
+    StartCreateCollection(),
+    StartDeleteItem(Box<dyn AnyItem>),
+    StartRenameItem(Box<dyn AnyItem>),
     StartRenameNote(),
+    StartRenameTag(String),
+    StartResolveConflict(Box<dyn AnyNote>),
+    RenameItem(Box<dyn AnyItem>, String),
     RenameNote(Box<dyn AnyNote>, String),
+    RenameTag {
+        from: String,
+        to: String,
+    },
+    Quit(),
+    ResolveConflict {
+        note: Box<dyn AnyNote>,
+        resolution: ConflictResolution,
+    },
+    RestoreItem(Box<dyn AnyItem>),
+    Search(String),
     SelectedCollectionPath(CollectionPath),
     SelectedItem(Box<dyn AnyItem>),
+    SetClipboard(ClipboardContent),
     SetMode(Mode),
     ShowAboutDialog(),
+    ShowError {
+        error: Error,
+        retry: Option<Box<AppMsg>>,
+    },
+    ShowTaggedNotes(String),
     ToggleMode(),
     Up(),
+    UpdateBacklinks(),
+    UpdateIndex(),
     UpdateItemList(),
 }
 
 #[relm4::component(pub, async)]
 impl AsyncComponent for App {
-    type Init = String;
+    type Init = Settings;
     type Input = AppMsg;
     type Output = ();
     type CommandOutput = ();
@@ -90,6 +749,12 @@ impl AsyncComponent for App {
             set_default_width: 600,
             set_default_height: 400,
 
+            connect_close_request[sender] => move |_| {
+                // closing waits for pending changes to be saved
+                sender.input(AppMsg::Quit());
+                glib::Propagation::Stop
+            },
+
             gtk::Box{
                 set_orientation: gtk::Orientation::Vertical,
                 adw::HeaderBar {
@@ -102,15 +767,26 @@ impl AsyncComponent for App {
                     },
                 },
 
-                gtk::Paned::new(gtk::Orientation::Horizontal) {
-                    set_position: 250,
-                    set_wide_handle: true,
+                #[local_ref]
+                banner -> adw::Banner {
+                    set_button_label: Some("Retry"),
+                    connect_button_clicked[sender] => move |_| {
+                        sender.input(AppMsg::UpdateItemList());
+                    },
+                },
 
-                    #[wrap(Some)]
-                    set_start_child = model.sidebar.widget() ,
+                #[local_ref]
+                toast_overlay -> adw::ToastOverlay {
+                    gtk::Paned::new(gtk::Orientation::Horizontal) {
+                        set_position: 250,
+                        set_wide_handle: true,
 
-                    #[wrap(Some)]
-                    set_end_child = model.note_view.widget(),
+                        #[wrap(Some)]
+                        set_start_child = model.sidebar.widget() ,
+
+                        #[wrap(Some)]
+                        set_end_child = model.note_view.widget(),
+                    }
                 }
             }
         }
@@ -118,6 +794,8 @@ impl AsyncComponent for App {
 
     menu! {
         main_menu: {
+            "New note" => NewNoteAction,
+            "New folder" => NewCollectionAction,
             "About" => AboutAction,
             section! {
                 "Quit" => QuitAction,
@@ -126,20 +804,33 @@ impl AsyncComponent for App {
     }
 
     async fn init(
-        storage_uri: Self::Init,
+        settings: Self::Init,
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
         let about_dialog_controller: Controller<AboutDialog> =
             AboutDialog::builder().launch(()).detach();
-        let storage = build_storage_from_url(storage_uri.clone().as_str())
-            .await
-            .unwrap_or_else(|_| panic!("could not build storage from URI {:?}", storage_uri));
+        let storage: Rc<dyn ItemStorage> =
+            match build_storage_from_url(settings.storage_uri.as_str(), &settings.file_rules).await
+            {
+                Ok(storage) => storage,
+                Err(err) => choose_storage(err, &settings.file_rules).await,
+            }
+            .into();
+        let index = Rc::new(SearchIndex::new(&storage.root().location()));
         let current_path = CollectionPath::from(storage.root());
 
+        note_resources::register_resource_scheme(storage.clone());
         let note_view: AsyncController<NoteView> = NoteView::builder()
-            .launch(())
+            .launch(storage.root().location())
             .forward(sender.input_sender(), identity);
+        let notebook_settings = NotebookSettings::load(&storage.root().location())
+            .await
+            .unwrap_or_else(|error| {
+                sender.input(AppMsg::ShowError { error, retry: None });
+                NotebookSettings::default()
+            });
+        note_view.emit(NoteViewMsg::SetMarkdownOptions(notebook_settings.markdown));
         let sidebar: AsyncController<Sidebar> = Sidebar::builder()
             .launch(current_path.clone())
             .forward(sender.input_sender(), identity);
@@ -147,15 +838,29 @@ impl AsyncComponent for App {
             .launch(())
             .forward(sender.input_sender(), identity);
 
+        let toast_overlay = adw::ToastOverlay::new();
+        let banner = adw::Banner::new("");
+
         let model = App {
             about_dialog_controller,
             storage,
+            index,
             sidebar,
             note_view,
             title_controller,
+            toast_overlay: toast_overlay.clone(),
+            banner: banner.clone(),
             current_path,
             current_note: None,
+            current_attachment: None,
+            current_etag: None,
+            current_format: TextFormat::default(),
+            autosave: Autosave::new(settings.autosave_delay),
+            collection_watch: None,
+            note_watch: None,
+            clipboard: None,
             mode: Mode::View,
+            file_rules: settings.file_rules.clone(),
         };
 
         let widgets = view_output!();
@@ -180,6 +885,19 @@ impl AsyncComponent for App {
             RelmAction::new_stateless(move |_| sender_clone.emit(SidebarMsg::FocusSearchEntry()));
         group.add_action(focus_search_entry_action);
 
+        let sender_clone = sender.clone();
+        let new_collection_action: RelmAction<NewCollectionAction> =
+            RelmAction::new_stateless(move |_| {
+                sender_clone.input(AppMsg::StartCreateCollection());
+            });
+        group.add_action(new_collection_action);
+
+        let sender_clone = sender.clone();
+        let new_note_action: RelmAction<NewNoteAction> = RelmAction::new_stateless(move |_| {
+            sender_clone.input(AppMsg::CreateNote());
+        });
+        group.add_action(new_note_action);
+
         let sender_clone = sender.clone();
         let toggle_action: RelmAction<ToggleModeAction> = RelmAction::new_stateless(move |_| {
             sender_clone.input(AppMsg::ToggleMode());
@@ -192,8 +910,9 @@ impl AsyncComponent for App {
         });
         group.add_action(up_action);
 
+        let sender_clone = sender.clone();
         let quit_action: RelmAction<QuitAction> = RelmAction::new_stateless(move |_| {
-            main_application().quit();
+            sender_clone.input(AppMsg::Quit());
         });
         group.add_action(quit_action);
 
@@ -202,11 +921,14 @@ impl AsyncComponent for App {
         let app = main_application();
         app.set_accelerators_for_action::<FocusNoteListAction>(&["<Control>L"]);
         app.set_accelerators_for_action::<FocusSearchEntryAction>(&["<Control>K"]);
+        app.set_accelerators_for_action::<NewCollectionAction>(&["<Control><Shift>N"]);
+        app.set_accelerators_for_action::<NewNoteAction>(&["<Control>N"]);
         app.set_accelerators_for_action::<QuitAction>(&["<Control>Q"]);
         app.set_accelerators_for_action::<ToggleModeAction>(&["<Control>Return"]);
         app.set_accelerators_for_action::<UpAction>(&["<Control>Up"]);
 
         sender.input(AppMsg::UpdateItemList());
+        sender.input(AppMsg::UpdateIndex());
 
         AsyncComponentParts { model, widgets }
     }
@@ -232,22 +954,7 @@ impl AsyncComponent for App {
             AppMsg::SelectedItem(item) => match item.kind() {
                 ItemKind::Note => {
                     let note = item.as_note().expect("note");
-                    let result = self.storage.as_ref().load_content(&*note).await;
-                    if let Ok(content) = result {
-                        self.current_note = Some(note.clone());
-                        self.note_view.emit(NoteViewMsg::LoadedNote {
-                            note,
-                            content: content.content,
-                        });
-                        self.title_controller
-                            .emit(TitleMsg::SetCurrentNote(self.current_note.clone()));
-                    } else {
-                        panic!(
-                            "tried to load content from non-note {:?}: {:?}",
-                            note,
-                            result.err()
-                        );
-                    }
+                    self.open_note(note, &sender).await;
                 }
                 ItemKind::Collection => {
                     let collection = item.as_collection().expect("collection");
@@ -256,21 +963,491 @@ impl AsyncComponent for App {
                         .emit(SidebarMsg::SetCollectionPath(self.current_path.clone()));
                     sender.input(AppMsg::UpdateItemList());
                 }
-                _ => {}
+                ItemKind::Attachment => {
+                    let attachment = item.as_attachment().expect("attachment");
+                    self.open_attachment(attachment, &sender).await;
+                }
             },
             AppMsg::ContentChanged { note, content } => {
-                let _ = self
+                let is_current_note = self
+                    .current_note
+                    .as_ref()
+                    .is_some_and(|current_note| current_note.location() == note.location());
+
+                if !is_current_note {
+                    // closed in the meantime, e.g. because it was deleted
+                } else {
+                    // while a conflict is resolved, this keeps the edits for it
+                    let sender = sender.clone();
+                    self.autosave.schedule(note, content, move |generation| {
+                        sender.input(AppMsg::AutosaveTimeout(generation));
+                    });
+                    self.title_controller
+                        .emit(TitleMsg::SetSaveState(SaveState::Unsaved));
+                }
+            }
+            AppMsg::CollectionChanged(event) => {
+                self.update_index_for(event.clone(), &sender);
+                self.sidebar.emit(SidebarMsg::ApplyItemEvent(event));
+            }
+            AppMsg::NoteChanged(event) => {
+                self.update_index_for(event.clone(), &sender);
+                let Some(note) = self.current_note.clone() else {
+                    return;
+                };
+
+                match event {
+                    ItemEvent::Deleted { location } if location == note.location() => {
+                        let toast = adw::Toast::builder()
+                            .title(glib::markup_escape_text(&format!(
+                                "“{}” was deleted elsewhere",
+                                note.name()
+                            )))
+                            .build();
+                        self.toast_overlay.add_toast(toast);
+                    }
+                    ItemEvent::Moved { location, item }
+                        if location == note.location() && item.location() != location =>
+                    {
+                        self.item_relocated(note.as_ref(), item, &sender).await;
+                    }
+                    _ => self.reload_note(&sender).await,
+                }
+            }
+            AppMsg::Search(query) => {
+                let scope = CollectionPath::from(self.storage.root());
+                let result = self
+                    .index
+                    .search(self.storage.as_ref(), &query, &scope)
+                    .await;
+                match result {
+                    Ok(results) => self
+                        .sidebar
+                        .emit(SidebarMsg::ShowSearchResults { query, results }),
+                    Err(error) => sender.input(AppMsg::ShowError {
+                        error,
+                        retry: Some(Box::new(AppMsg::Search(query))),
+                    }),
+                }
+            }
+            AppMsg::UpdateIndex() => {
+                // runs in the background, searches meanwhile scan the notes directly
+                let index = self.index.clone();
+                let storage = self.storage.clone();
+                relm4::spawn_local(async move {
+                    match index.update(storage.as_ref()).await {
+                        Ok(()) => sender.input(AppMsg::IndexUpdated()),
+                        Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                    }
+                });
+            }
+            AppMsg::IndexUpdated() => {
+                let wiki_targets = WikiTargets::new(
+                    self.index
+                        .notes()
+                        .into_iter()
+                        .map(|(note, path)| (note.name(), path.names_below_root())),
+                );
+                self.note_view
+                    .emit(NoteViewMsg::SetWikiTargets(wiki_targets));
+
+                match self.index.tags(self.storage.as_ref()).await {
+                    Ok(tags) => self.sidebar.emit(SidebarMsg::SetTags(tags)),
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
+                sender.input(AppMsg::UpdateBacklinks());
+            }
+            AppMsg::UpdateBacklinks() => {
+                let Some(note) = self.current_note.clone() else {
+                    return;
+                };
+
+                match self
+                    .index
+                    .backlinks(self.storage.as_ref(), note.as_ref())
+                    .await
+                {
+                    Ok(backlinks) => self.note_view.emit(NoteViewMsg::SetBacklinks(backlinks)),
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
+            }
+            AppMsg::OpenLocalLink(uri) => {
+                let location = uri.split(['#', '?']).next().unwrap_or_default();
+                match self.find_item(location).await {
+                    Ok(Some((path, item))) => {
+                        if item.kind() == ItemKind::Collection {
+                            let mut path = path;
+                            path.push(item.as_collection().expect("collection"));
+                            sender.input(AppMsg::SelectedCollectionPath(path));
+                        } else {
+                            if path != self.current_path {
+                                sender.input(AppMsg::SelectedCollectionPath(path));
+                            }
+                            sender.input(AppMsg::SelectedItem(item));
+                        }
+                    }
+                    Ok(None) => {
+                        let name = gio::File::for_uri(location)
+                            .basename()
+                            .map_or(uri.clone(), |name| name.display().to_string());
+                        let toast = adw::Toast::builder()
+                            .title(glib::markup_escape_text(&format!(
+                                "“{}” was not found in this notebook",
+                                name
+                            )))
+                            .build();
+                        self.toast_overlay.add_toast(toast);
+                    }
+                    Err(error) => sender.input(AppMsg::ShowError {
+                        error,
+                        retry: Some(Box::new(AppMsg::OpenLocalLink(uri))),
+                    }),
+                }
+            }
+            AppMsg::OpenWikiLink(target) => {
+                // prefer a note next to the current one if several match
+                let current_location = self.current_path.last().location();
+                let mut candidates: Vec<(Box<dyn AnyNote>, CollectionPath)> = self
+                    .index
+                    .notes()
+                    .into_iter()
+                    .filter(|(note, path)| {
+                        wiki::links_to(&target, &note.name(), &path.names_below_root())
+                    })
+                    .collect();
+                candidates.sort_by_key(|(_, path)| path.last().location() != current_location);
+
+                match candidates.into_iter().next() {
+                    Some((note, _)) => self.open_note(note, &sender).await,
+                    None => self.create_linked_note(&target, &sender).await,
+                }
+            }
+            AppMsg::ShowTaggedNotes(tag) => {
+                let result = self.index.notes_with_tag(self.storage.as_ref(), &tag).await;
+                match result {
+                    Ok(results) => self.sidebar.emit(SidebarMsg::ShowSearchResults {
+                        query: tag,
+                        results,
+                    }),
+                    Err(error) => sender.input(AppMsg::ShowError {
+                        error,
+                        retry: Some(Box::new(AppMsg::ShowTaggedNotes(tag))),
+                    }),
+                }
+            }
+            AppMsg::StartRenameTag(tag) => {
+                let root = root.clone();
+                relm4::spawn_local(async move {
+                    let heading = format!("Rename tag “{}”", tag);
+                    if let Some(name) = prompt_for_name(&root, &heading, "Rename", &tag).await {
+                        let name = name.trim().trim_start_matches('#').to_string();
+                        if !name.is_empty() && name != tag {
+                            sender.input(AppMsg::RenameTag {
+                                from: tag,
+                                to: name,
+                            });
+                        }
+                    }
+                });
+            }
+            AppMsg::RenameTag { from, to } => {
+                self.rename_tag(&from, &to, &sender).await;
+            }
+            AppMsg::OpenSearchResult {
+                search_match,
+                query,
+            } => {
+                let location = search_match.note.location();
+                self.open_note(search_match.note, &sender).await;
+
+                let is_open = self
+                    .current_note
+                    .as_ref()
+                    .is_some_and(|note| note.location() == location);
+                if is_open {
+                    self.note_view.emit(NoteViewMsg::Highlight(Highlight {
+                        query,
+                        line_number: search_match.line_number,
+                    }));
+                }
+            }
+            AppMsg::AutosaveTimeout(generation) => {
+                if let Some((note, content)) = self.autosave.timed_out(generation) {
+                    let etag = self.current_etag.clone();
+                    self.save_note(note, content, etag, &sender).await;
+                }
+            }
+            AppMsg::StartResolveConflict(note) => {
+                let root = root.clone();
+                relm4::spawn_local(async move {
+                    let resolution = resolve_conflict(&root, &note.name()).await;
+                    sender.input(AppMsg::ResolveConflict { note, resolution });
+                });
+            }
+            AppMsg::ResolveConflict { note, resolution } => {
+                let Some(content) = self.autosave.resolve(note.as_ref()) else {
+                    return;
+                };
+
+                match resolution {
+                    ConflictResolution::KeepMine => {
+                        self.save_note(note, content, None, &sender).await;
+                    }
+                    ConflictResolution::TakeTheirs => {
+                        self.open_note(note, &sender).await;
+                        sender.input(AppMsg::SetMode(self.mode.clone()));
+                    }
+                    ConflictResolution::Merge => {
+                        match self.storage.as_ref().load_content(note.as_ref()).await {
+                            Ok(theirs) => {
+                                // saving the merged content later must not conflict again
+                                self.current_etag = theirs.etag;
+                                self.current_format = theirs.format;
+                                let merged = merge_with_conflict_markers(&content, &theirs.content);
+                                self.note_view.emit(NoteViewMsg::LoadedNote {
+                                    note: note.clone(),
+                                    content: merged.clone(),
+                                });
+                                sender.input(AppMsg::SetMode(Mode::Edit));
+                                // the merged content is saved like any other edit
+                                sender.input(AppMsg::ContentChanged {
+                                    note,
+                                    content: merged,
+                                });
+                            }
+                            Err(error) => {
+                                self.autosave.conflicted(note.clone(), content);
+                                sender.input(AppMsg::ShowError {
+                                    error,
+                                    retry: Some(Box::new(AppMsg::ResolveConflict {
+                                        note,
+                                        resolution,
+                                    })),
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+            AppMsg::CreateNote() => match self.create_new_note().await {
+                Ok(note) => {
+                    self.update_note_list(&sender).await;
+                    self.open_note(note, &sender).await;
+                    sender.input(AppMsg::SetMode(Mode::Edit));
+                }
+                Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+            },
+            AppMsg::StartCreateCollection() => {
+                let root = root.clone();
+                relm4::spawn_local(async move {
+                    if let Some(name) = prompt_for_name(&root, "New folder", "Create", "").await {
+                        sender.input(AppMsg::CreateCollection(name));
+                    }
+                });
+            }
+            AppMsg::CreateCollection(name) => {
+                let result = self
+                    .storage
+                    .as_ref()
+                    .create_collection(self.current_path.last().as_ref(), &name)
+                    .await;
+                match result {
+                    Ok(_) => self.update_note_list(&sender).await,
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
+            }
+            AppMsg::StartDeleteItem(item) => {
+                let root = root.clone();
+                relm4::spawn_local(async move {
+                    let heading = format!("Delete “{}”?", item.name());
+                    let body = "It will be moved to the trash.";
+                    if confirm_destructive(&root, &heading, body, "Delete").await {
+                        sender.input(AppMsg::DeleteItem {
+                            item,
+                            permanently: false,
+                        });
+                    }
+                });
+            }
+            AppMsg::DeleteItem { item, permanently } => {
+                let result = self
+                    .storage
+                    .as_ref()
+                    .delete_item(item.as_ref(), permanently)
+                    .await;
+                match result {
+                    Ok(()) => {
+                        self.close_note_if_inside(&item.location());
+                        self.update_note_list(&sender).await;
+
+                        if !permanently {
+                            let toast = adw::Toast::builder()
+                                .title(glib::markup_escape_text(&format!(
+                                    "“{}” moved to trash",
+                                    item.name()
+                                )))
+                                .button_label("Undo")
+                                .build();
+                            let sender = sender.clone();
+                            toast.connect_button_clicked(move |_| {
+                                sender.input(AppMsg::RestoreItem(item.clone()));
+                            });
+                            self.toast_overlay.add_toast(toast);
+                        }
+                    }
+                    Err(err) if matches!(err.cause(), Error::TrashNotSupported { .. }) => {
+                        let root = root.clone();
+                        relm4::spawn_local(async move {
+                            let heading = format!("Delete “{}” permanently?", item.name());
+                            let body = "The trash is not available here. This cannot be undone.";
+                            if confirm_destructive(&root, &heading, body, "Delete").await {
+                                // folders get their own confirmation in case they aren't empty
+                                sender.input(match item.as_collection() {
+                                    Some(collection) => AppMsg::DeleteCollection {
+                                        collection,
+                                        recursive: false,
+                                    },
+                                    None => AppMsg::DeleteItem {
+                                        item,
+                                        permanently: true,
+                                    },
+                                });
+                            }
+                        });
+                    }
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
+            }
+            AppMsg::SetClipboard(content) => {
+                self.clipboard = Some(content);
+            }
+            AppMsg::Paste() => {
+                let destination = self.current_path.last().clone();
+                match self.clipboard.take() {
+                    Some(ClipboardContent::Cut(item)) => {
+                        sender.input(AppMsg::MoveItem { item, destination });
+                    }
+                    Some(ClipboardContent::Copied(item)) => {
+                        self.clipboard = Some(ClipboardContent::Copied(item.clone()));
+                        sender.input(AppMsg::CopyItem { item, destination });
+                    }
+                    None => {}
+                }
+            }
+            AppMsg::MoveItem { item, destination } => {
+                let result = self
+                    .storage
+                    .as_ref()
+                    .move_item(item.as_ref(), destination.as_ref())
+                    .await;
+                match result {
+                    Ok(moved) => {
+                        self.item_relocated(item.as_ref(), moved, &sender).await;
+                        self.update_note_list(&sender).await;
+                    }
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
+            }
+            AppMsg::CopyItem { item, destination } => {
+                let result = self
                     .storage
                     .as_ref()
-                    .save_content(
-                        note.as_ref(),
-                        &NoteContent {
-                            content,
-                            etag: None,
-                        },
-                    )
+                    .copy_item(item.as_ref(), destination.as_ref())
                     .await;
+                match result {
+                    Ok(_) => self.update_note_list(&sender).await,
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
             }
+            AppMsg::RestoreItem(item) => {
+                let result = self.storage.as_ref().restore_item(item.as_ref()).await;
+                match result {
+                    Ok(()) => self.update_note_list(&sender).await,
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
+            }
+            AppMsg::DeleteCollection {
+                collection,
+                recursive,
+            } => {
+                let result = self
+                    .storage
+                    .as_ref()
+                    .delete_collection(collection.as_ref(), recursive)
+                    .await;
+                match result {
+                    Ok(()) => {
+                        self.close_note_if_inside(&collection.location());
+                        self.update_note_list(&sender).await;
+                    }
+                    Err(err) if matches!(err.cause(), Error::NotEmpty { .. }) => {
+                        let root = root.clone();
+                        relm4::spawn_local(async move {
+                            let heading = format!("“{}” is not empty", collection.name());
+                            let body =
+                                "Delete the folder and everything in it? This cannot be undone.";
+                            if confirm_destructive(&root, &heading, body, "Delete All").await {
+                                sender.input(AppMsg::DeleteCollection {
+                                    collection,
+                                    recursive: true,
+                                });
+                            }
+                        });
+                    }
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                }
+            }
+            AppMsg::StartRenameItem(item) => {
+                let heading = match item.kind() {
+                    ItemKind::Note => "Rename note",
+                    ItemKind::Collection => "Rename folder",
+                    ItemKind::Attachment => return,
+                };
+                let root = root.clone();
+                relm4::spawn_local(async move {
+                    if let Some(new_name) =
+                        prompt_for_name(&root, heading, "Rename", &item.name()).await
+                    {
+                        sender.input(AppMsg::RenameItem(item, new_name));
+                    }
+                });
+            }
+            AppMsg::RenameItem(item, new_name) => match item.kind() {
+                ItemKind::Note => {
+                    let note = item.as_note().expect("note");
+                    let result = self
+                        .storage
+                        .as_ref()
+                        .rename_note(note.as_ref(), &new_name)
+                        .await;
+                    match result {
+                        Ok(renamed_note) => {
+                            self.item_relocated(note.as_ref(), renamed_note.clone_box(), &sender)
+                                .await;
+                            self.update_note_list(&sender).await;
+                        }
+                        Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                    }
+                }
+                ItemKind::Collection => {
+                    let collection = item.as_collection().expect("collection");
+                    let result = self
+                        .storage
+                        .as_ref()
+                        .rename_collection(collection.as_ref(), &new_name)
+                        .await;
+                    match result {
+                        Ok(renamed) => {
+                            self.item_relocated(collection.as_ref(), renamed.clone_box(), &sender)
+                                .await;
+                            self.collection_renamed(collection.as_ref(), renamed, &sender)
+                                .await
+                        }
+                        Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
+                    }
+                }
+                ItemKind::Attachment => {}
+            },
             AppMsg::StartRenameNote() => {
                 self.title_controller
                     .emit(TitleMsg::SetMode(TitleMode::EditTitle));
@@ -281,37 +1458,66 @@ impl AsyncComponent for App {
                     .as_ref()
                     .rename_note(note.as_ref(), &new_name)
                     .await;
-                if let Ok(renamed_note) = result {
-                    self.current_note = Some(renamed_note);
-                    self.title_controller
-                        .emit(TitleMsg::SetCurrentNote(self.current_note.clone()));
-                    self.title_controller
-                        .emit(TitleMsg::SetMode(TitleMode::Normal));
-                    self.update_note_list(&self.current_path).await;
-                } else {
-                    panic!(
-                        "rename note from {} to {} failed: {:?}",
-                        note.name(),
-                        new_name,
-                        result.err()
-                    );
+                match result {
+                    Ok(renamed_note) => {
+                        self.item_relocated(note.as_ref(), renamed_note.clone_box(), &sender)
+                            .await;
+                        self.update_note_list(&sender).await;
+                    }
+                    Err(error) => sender.input(AppMsg::ShowError { error, retry: None }),
                 }
+                self.title_controller
+                    .emit(TitleMsg::SetMode(TitleMode::Normal));
             }
             AppMsg::UpdateItemList() => {
-                self.update_note_list(&self.current_path).await;
+                self.update_note_list(&sender).await;
+            }
+            AppMsg::Quit() => {
+                if self.flush_autosave(&sender).await {
+                    main_application().quit();
+                } else if !self.autosave.has_conflict() {
+                    let root = root.clone();
+                    relm4::spawn_local(async move {
+                        let confirmed = confirm_destructive(
+                            &root,
+                            "Quit without saving?",
+                            "The latest changes to the current note could not be saved.",
+                            "Quit",
+                        )
+                        .await;
+                        if confirmed {
+                            main_application().quit();
+                        }
+                    });
+                }
             }
             AppMsg::SetMode(mode) => {
+                self.flush_autosave(&sender).await;
                 self.mode = mode;
                 self.note_view.emit(NoteViewMsg::SetMode(self.mode.clone()));
             }
+            AppMsg::ShowError { error, retry } => {
+                self.show_error(error, retry.map(|retry| *retry), &sender);
+            }
             AppMsg::ShowAboutDialog() => {
                 self.about_dialog_controller
                     .emit(AboutDialogMsg::Show(root.clone()));
             }
             AppMsg::ToggleMode() => {
+                self.flush_autosave(&sender).await;
                 self.mode = self.mode.toggled();
                 self.note_view.emit(NoteViewMsg::SetMode(self.mode.clone()));
             }
+            AppMsg::EditMetadata { title, tags } => {
+                self.note_view
+                    .emit(NoteViewMsg::EditMetadata { title, tags });
+            }
+            AppMsg::EditorScrolled(line) => {
+                self.note_view.emit(NoteViewMsg::EditorScrolled(line));
+            }
+            AppMsg::ToggleTask(offset) => {
+                self.note_view.emit(NoteViewMsg::ToggleTask(offset));
+            }
             AppMsg::NoteContentChanged(content) => {
                 self.note_view.emit(NoteViewMsg::ContentChanged(content));
                 // self.etag = self
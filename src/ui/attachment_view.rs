@@ -0,0 +1,391 @@
+use gtk::{gdk, gio, glib, prelude::*};
+use relm4::{Component, ComponentParts, ComponentSender, RelmWidgetExt};
+
+use crate::{
+    errors::Error,
+    icon_names,
+    persistence::{
+        models::AnyAttachment,
+        storage::{AttachmentInfo, ItemStorage},
+    },
+    ui::app::AppMsg,
+};
+
+const ZOOM_STEP: f64 = 1.25;
+const MIN_ZOOM: f64 = 0.1;
+const MAX_ZOOM: f64 = 8.0;
+
+/// What can be shown of an attachment's content.
+#[derive(Debug, Clone, Default)]
+pub enum Preview {
+    Image(gdk::Texture),
+    Media(gtk::MediaFile),
+    #[default]
+    None,
+}
+
+impl Preview {
+    /// Loads what's needed to show `attachment`. Images are decoded right
+    /// away, audio and video are streamed while playing.
+    pub async fn load(
+        storage: &dyn ItemStorage,
+        attachment: &dyn AnyAttachment,
+        info: &AttachmentInfo,
+    ) -> Result<Preview, Error> {
+        let preview = match mime_type(&info.content_type).split('/').next() {
+            Some("image") => {
+                let bytes = storage.load_bytes(attachment).await?;
+                // formats GTK can't decode only get the details
+                gdk::Texture::from_bytes(&glib::Bytes::from_owned(bytes))
+                    .map_or(Preview::None, Preview::Image)
+            }
+            Some("audio" | "video") => {
+                let stream = storage.read_stream(attachment).await?;
+                Preview::Media(gtk::MediaFile::for_input_stream(&stream))
+            }
+            _ => Preview::None,
+        };
+
+        Ok(preview)
+    }
+
+    fn texture(&self) -> Option<&gdk::Texture> {
+        match self {
+            Preview::Image(texture) => Some(texture),
+            _ => None,
+        }
+    }
+
+    fn media(&self) -> Option<&gtk::MediaFile> {
+        match self {
+            Preview::Media(media) => Some(media),
+            _ => None,
+        }
+    }
+}
+
+fn mime_type(content_type: &str) -> String {
+    gio::content_type_get_mime_type(content_type)
+        .map_or(content_type.to_string(), |mime_type| mime_type.to_string())
+}
+
+#[tracker::track]
+pub struct AttachmentView {
+    #[do_not_track]
+    attachment: Option<Box<dyn AnyAttachment>>,
+    #[no_eq]
+    info: Option<AttachmentInfo>,
+    #[no_eq]
+    preview: Preview,
+    /// scale of the image, or `None` to fit it into the view
+    zoom: Option<f64>,
+}
+
+#[derive(Debug)]
+pub enum AttachmentViewMsg {
+    Clear(),
+    OpenWith(),
+    Show {
+        attachment: Box<dyn AnyAttachment>,
+        info: AttachmentInfo,
+        preview: Preview,
+    },
+    ZoomIn(),
+    ZoomOut(),
+    ZoomToFit(),
+}
+
+impl AttachmentView {
+    fn details(&self) -> (String, String, String) {
+        let Some((attachment, info)) = self.attachment.as_ref().zip(self.info.as_ref()) else {
+            return Default::default();
+        };
+        let modified = attachment
+            .updated_at()
+            .to_local()
+            .and_then(|updated_at| updated_at.format("%x %X"))
+            .map_or(String::from("unknown"), |updated_at| updated_at.to_string());
+
+        (
+            glib::format_size(info.size).to_string(),
+            mime_type(&info.content_type),
+            modified,
+        )
+    }
+
+    fn zoom_label(&self) -> String {
+        match self.zoom {
+            Some(zoom) => format!("{:.0}%", zoom * 100.0),
+            None => String::from("Fit"),
+        }
+    }
+
+    /// The scale an image fitted into the view is shown at, as a starting
+    /// point for zooming.
+    fn fitted_zoom(&self, widgets: &AttachmentViewWidgets) -> f64 {
+        let Some(texture) = self.preview.texture() else {
+            return 1.0;
+        };
+
+        let scale = f64::min(
+            widgets.fitted_picture.width() as f64 / texture.width() as f64,
+            widgets.fitted_picture.height() as f64 / texture.height() as f64,
+        );
+        if scale > 0.0 { scale.min(1.0) } else { 1.0 }
+    }
+
+    fn zoom_by(&mut self, factor: f64, widgets: &AttachmentViewWidgets) {
+        let zoom = self.zoom.unwrap_or_else(|| self.fitted_zoom(widgets)) * factor;
+        self.set_zoom(Some(zoom.clamp(MIN_ZOOM, MAX_ZOOM)));
+    }
+
+    fn update_zoomed_size(&self, widgets: &AttachmentViewWidgets) {
+        match (self.preview.texture(), self.zoom) {
+            (Some(texture), Some(zoom)) => widgets.zoomed_picture.set_size_request(
+                (texture.width() as f64 * zoom).round() as i32,
+                (texture.height() as f64 * zoom).round() as i32,
+            ),
+            _ => widgets.zoomed_picture.set_size_request(-1, -1),
+        }
+    }
+}
+
+#[relm4::component(pub)]
+impl Component for AttachmentView {
+    type Init = ();
+    type Input = AttachmentViewMsg;
+    type Output = AppMsg;
+    type CommandOutput = ();
+
+    view! {
+        gtk::Box {
+            set_orientation: gtk::Orientation::Vertical,
+            set_hexpand: true,
+            set_vexpand: true,
+
+            gtk::Stack {
+                set_vexpand: true,
+
+                add_child = &gtk::Box {
+                    #[name = "fitted_picture"]
+                    gtk::Picture {
+                        set_hexpand: true,
+                        set_vexpand: true,
+                        set_can_shrink: true,
+                        set_content_fit: gtk::ContentFit::ScaleDown,
+                        #[track(model.changed(AttachmentView::preview()))]
+                        set_paintable: model.preview.texture(),
+                    },
+                } -> { set_name: "fitted" },
+                add_child = &gtk::ScrolledWindow {
+                    // pictures in a `gtk::Fixed` get exactly the size they ask for
+                    gtk::Fixed {
+                        set_halign: gtk::Align::Center,
+                        set_valign: gtk::Align::Center,
+
+                        #[name = "zoomed_picture"]
+                        put[0.0, 0.0] = &gtk::Picture {
+                            set_can_shrink: true,
+                            set_content_fit: gtk::ContentFit::Fill,
+                            #[track(model.changed(AttachmentView::preview()))]
+                            set_paintable: model.preview.texture(),
+                        },
+                    },
+                } -> { set_name: "zoomed" },
+                add_child = &gtk::Video {
+                    #[track(model.changed(AttachmentView::preview()))]
+                    set_media_stream: model.preview.media(),
+                } -> { set_name: "media" },
+                add_child = &gtk::Box {
+                    set_orientation: gtk::Orientation::Vertical,
+                    set_valign: gtk::Align::Center,
+                    set_spacing: 12,
+
+                    gtk::Image {
+                        set_pixel_size: 96,
+                        add_css_class: "dim-label",
+                        #[track(model.changed(AttachmentView::info()))]
+                        set_from_gicon: &model.info.as_ref().map_or(
+                            gio::ThemedIcon::new("text-x-generic").upcast(),
+                            |info| gio::content_type_get_icon(&info.content_type),
+                        ),
+                    },
+                    gtk::Label {
+                        add_css_class: "title-2",
+                        set_wrap: true,
+                        #[watch]
+                        set_label: &model.attachment.as_ref().map_or(String::new(), |attachment| attachment.name()),
+                    },
+                } -> { set_name: "file" },
+
+                #[track(model.changed(AttachmentView::preview() | AttachmentView::zoom()))]
+                set_visible_child_name: match (&model.preview, model.zoom) {
+                    (Preview::Image(_), None) => "fitted",
+                    (Preview::Image(_), Some(_)) => "zoomed",
+                    (Preview::Media(_), _) => "media",
+                    (Preview::None, _) => "file",
+                },
+            },
+
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_margin_all: 12,
+                set_spacing: 24,
+                add_css_class: "card",
+
+                gtk::Grid {
+                    set_margin_all: 12,
+                    set_row_spacing: 4,
+                    set_column_spacing: 12,
+                    set_hexpand: true,
+
+                    attach[0, 0, 1, 1] = &gtk::Label {
+                        set_label: "Size",
+                        set_halign: gtk::Align::Start,
+                        add_css_class: "dim-label",
+                    },
+                    attach[1, 0, 1, 1] = &gtk::Label {
+                        set_halign: gtk::Align::Start,
+                        set_selectable: true,
+                        #[track(model.changed(AttachmentView::info()))]
+                        set_label: &model.details().0,
+                    },
+                    attach[0, 1, 1, 1] = &gtk::Label {
+                        set_label: "Type",
+                        set_halign: gtk::Align::Start,
+                        add_css_class: "dim-label",
+                    },
+                    attach[1, 1, 1, 1] = &gtk::Label {
+                        set_halign: gtk::Align::Start,
+                        set_selectable: true,
+                        #[track(model.changed(AttachmentView::info()))]
+                        set_label: &model.details().1,
+                    },
+                    attach[0, 2, 1, 1] = &gtk::Label {
+                        set_label: "Modified",
+                        set_halign: gtk::Align::Start,
+                        add_css_class: "dim-label",
+                    },
+                    attach[1, 2, 1, 1] = &gtk::Label {
+                        set_halign: gtk::Align::Start,
+                        set_selectable: true,
+                        #[track(model.changed(AttachmentView::info()))]
+                        set_label: &model.details().2,
+                    },
+                },
+
+                gtk::Box {
+                    set_orientation: gtk::Orientation::Horizontal,
+                    set_valign: gtk::Align::Center,
+                    add_css_class: "linked",
+                    #[track(model.changed(AttachmentView::preview()))]
+                    set_visible: model.preview.texture().is_some(),
+
+                    gtk::Button {
+                        set_icon_name: icon_names::ZOOM_OUT_REGULAR,
+                        set_tooltip_text: Some("Zoom out"),
+                        connect_clicked => AttachmentViewMsg::ZoomOut(),
+                    },
+                    gtk::Button {
+                        set_tooltip_text: Some("Fit to view"),
+                        set_width_request: 64,
+                        #[track(model.changed(AttachmentView::zoom()))]
+                        set_label: &model.zoom_label(),
+                        connect_clicked => AttachmentViewMsg::ZoomToFit(),
+                    },
+                    gtk::Button {
+                        set_icon_name: icon_names::ZOOM_IN_REGULAR,
+                        set_tooltip_text: Some("Zoom in"),
+                        connect_clicked => AttachmentViewMsg::ZoomIn(),
+                    },
+                },
+
+                gtk::Button {
+                    set_label: "Open with…",
+                    set_valign: gtk::Align::Center,
+                    set_margin_end: 12,
+                    connect_clicked => AttachmentViewMsg::OpenWith(),
+                },
+            },
+        }
+    }
+
+    fn init(
+        _: Self::Init,
+        root: Self::Root,
+        sender: ComponentSender<Self>,
+    ) -> ComponentParts<Self> {
+        let model = AttachmentView {
+            attachment: None,
+            info: None,
+            preview: Preview::None,
+            zoom: None,
+            tracker: 0,
+        };
+
+        let widgets = view_output!();
+
+        ComponentParts { model, widgets }
+    }
+
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        msg: Self::Input,
+        sender: ComponentSender<Self>,
+        root: &Self::Root,
+    ) {
+        self.reset();
+
+        match msg {
+            AttachmentViewMsg::Clear() => {
+                if let Some(media) = self.preview.media() {
+                    media.set_playing(false);
+                }
+                self.attachment = None;
+                self.set_info(None);
+                self.set_preview(Preview::None);
+                self.set_zoom(None);
+            }
+            AttachmentViewMsg::OpenWith() => {
+                if let Some(attachment) = &self.attachment {
+                    let launcher =
+                        gtk::FileLauncher::new(Some(&gio::File::for_uri(&attachment.location())));
+                    launcher.set_always_ask(true);
+                    let window = root.root().and_downcast::<gtk::Window>();
+                    let sender = sender.clone();
+                    relm4::spawn_local(async move {
+                        if let Err(error) = launcher.launch_future(window.as_ref()).await {
+                            // dismissing the chooser is not an error
+                            if !error.matches(gtk::DialogError::Dismissed) {
+                                let _ = sender.output(AppMsg::ShowError {
+                                    error: error.into(),
+                                    retry: None,
+                                });
+                            }
+                        }
+                    });
+                }
+            }
+            AttachmentViewMsg::Show {
+                attachment,
+                info,
+                preview,
+            } => {
+                if let Some(media) = self.preview.media() {
+                    media.set_playing(false);
+                }
+                self.attachment = Some(attachment);
+                self.set_info(Some(info));
+                self.set_preview(preview);
+                self.set_zoom(None);
+            }
+            AttachmentViewMsg::ZoomIn() => self.zoom_by(ZOOM_STEP, widgets),
+            AttachmentViewMsg::ZoomOut() => self.zoom_by(1.0 / ZOOM_STEP, widgets),
+            AttachmentViewMsg::ZoomToFit() => self.set_zoom(None),
+        }
+
+        self.update_zoomed_size(widgets);
+        self.update_view(widgets, sender);
+    }
+}
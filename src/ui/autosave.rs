@@ -0,0 +1,183 @@
+use std::time::Duration;
+
+use gtk::glib;
+
+use crate::persistence::models::AnyNote;
+
+/// Debounces note saves: content is only handed out for saving after no
+/// further changes came in for `delay`, or when it is flushed explicitly.
+/// Content always travels with the note it belongs to, so that it can't end
+/// up in another note after switching.
+pub struct Autosave {
+    delay: Duration,
+    pending: Option<(Box<dyn AnyNote>, String)>,
+    // bumped on every change, so that earlier timeouts can be told apart
+    generation: u64,
+    /// content that could not be saved because the note was changed
+    /// elsewhere, until the user resolves the conflict
+    conflict: Option<(Box<dyn AnyNote>, String)>,
+}
+
+impl Autosave {
+    pub fn new(delay: Duration) -> Self {
+        Self {
+            delay,
+            pending: None,
+            generation: 0,
+            conflict: None,
+        }
+    }
+
+    /// Remembers `content` of `note` for saving and calls `on_timeout` with
+    /// the current generation once the delay has passed. While a conflict on
+    /// `note` is being resolved, the conflicting content is replaced instead.
+    pub fn schedule(
+        &mut self,
+        note: Box<dyn AnyNote>,
+        content: String,
+        on_timeout: impl FnOnce(u64) + 'static,
+    ) {
+        if let Some((conflicting_note, conflicting_content)) = self.conflict.as_mut()
+            && conflicting_note.location() == note.location()
+        {
+            *conflicting_content = content;
+            return;
+        }
+
+        self.pending = Some((note, content));
+        self.generation += 1;
+
+        let generation = self.generation;
+        glib::timeout_add_local_once(self.delay, move || on_timeout(generation));
+    }
+
+    /// Returns the pending content if no changes came in since the timeout
+    /// for `generation` was scheduled.
+    pub fn timed_out(&mut self, generation: u64) -> Option<(Box<dyn AnyNote>, String)> {
+        if generation == self.generation {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
+
+    /// Returns the pending content right away, if there is any.
+    pub fn flush(&mut self) -> Option<(Box<dyn AnyNote>, String)> {
+        self.pending.take()
+    }
+
+    /// Holds on to `content` of `note` until the conflict it caused when
+    /// saving is resolved.
+    pub fn conflicted(&mut self, note: Box<dyn AnyNote>, content: String) {
+        self.conflict = Some((note, content));
+    }
+
+    pub fn has_conflict(&self) -> bool {
+        self.conflict.is_some()
+    }
+
+    /// Returns the conflicting content of `note` for resolving the conflict.
+    pub fn resolve(&mut self, note: &dyn AnyNote) -> Option<String> {
+        match self.conflict.take() {
+            Some((conflicting_note, content)) if conflicting_note.location() == note.location() => {
+                Some(content)
+            }
+            conflict => {
+                self.conflict = conflict;
+                None
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::any::Any;
+
+    use gtk::glib::DateTime;
+
+    use super::*;
+    use crate::persistence::frontmatter::Metadata;
+    use crate::persistence::models::{AnyAttachment, AnyCollection, AnyItem, ItemKind};
+
+    #[derive(Debug, Clone)]
+    struct TestNote {
+        name: String,
+    }
+
+    impl AnyItem for TestNote {
+        fn kind(&self) -> ItemKind {
+            ItemKind::Note
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_note(&self) -> Option<Box<dyn AnyNote>> {
+            Some(Box::new(self.clone()))
+        }
+
+        fn as_collection(&self) -> Option<Box<dyn AnyCollection>> {
+            None
+        }
+
+        fn as_attachment(&self) -> Option<Box<dyn AnyAttachment>> {
+            None
+        }
+
+        fn clone_box(&self) -> Box<dyn AnyItem> {
+            Box::new(self.clone())
+        }
+
+        fn name(&self) -> String {
+            self.name.clone()
+        }
+
+        fn updated_at(&self) -> DateTime {
+            DateTime::now_utc().unwrap()
+        }
+
+        fn location(&self) -> String {
+            format!("/somewhere/over/the/filesystem/{}", self.name)
+        }
+    }
+
+    impl AnyNote for TestNote {
+        fn metadata(&self) -> Metadata {
+            Metadata::default()
+        }
+
+        fn etag(&self) -> Option<String> {
+            None
+        }
+    }
+
+    fn note(name: &str) -> Box<dyn AnyNote> {
+        Box::new(TestNote {
+            name: name.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_switch_notes_with_pending_conflict() {
+        let mut autosave = Autosave::new(Duration::from_secs(60));
+        autosave.schedule(note("a.md"), "mine".to_string(), |_| {});
+        let (saved_note, content) = autosave.flush().unwrap();
+        assert_eq!(saved_note.name(), "a.md");
+        autosave.conflicted(saved_note, content);
+
+        // switching notes has to wait, while editing a carries on
+        assert!(autosave.has_conflict());
+        autosave.schedule(note("a.md"), "mine, edited".to_string(), |_| {});
+        assert!(autosave.flush().is_none());
+
+        // resolving for another note must not hand out a's content
+        assert_eq!(autosave.resolve(note("b.md").as_ref()), None);
+        assert_eq!(
+            autosave.resolve(note("a.md").as_ref()).as_deref(),
+            Some("mine, edited")
+        );
+        assert!(!autosave.has_conflict());
+    }
+}
@@ -0,0 +1,124 @@
+use adw::prelude::*;
+
+use crate::errors::Error;
+
+const RESPONSE_CANCEL: &str = "cancel";
+const RESPONSE_CONFIRM: &str = "confirm";
+const RESPONSE_MERGE: &str = "merge";
+const RESPONSE_MINE: &str = "mine";
+const RESPONSE_THEIRS: &str = "theirs";
+
+/// Asks for a name, e.g. for creating or renaming an item. Returns `None` if
+/// the dialog was cancelled or the entered name is empty.
+pub async fn prompt_for_name(
+    parent: &impl IsA<gtk::Widget>,
+    heading: &str,
+    confirm_label: &str,
+    initial_name: &str,
+) -> Option<String> {
+    let entry = gtk::Entry::builder()
+        .text(initial_name)
+        .activates_default(true)
+        .build();
+    let dialog = adw::AlertDialog::builder()
+        .heading(heading)
+        .extra_child(&entry)
+        .close_response(RESPONSE_CANCEL)
+        .default_response(RESPONSE_CONFIRM)
+        .build();
+    dialog.add_responses(&[
+        (RESPONSE_CANCEL, "Cancel"),
+        (RESPONSE_CONFIRM, confirm_label),
+    ]);
+    dialog.set_response_appearance(RESPONSE_CONFIRM, adw::ResponseAppearance::Suggested);
+
+    let response = dialog.choose_future(parent).await;
+    let name = entry.text().trim().to_string();
+
+    if response == RESPONSE_CONFIRM && !name.is_empty() {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// Asks the user to confirm a destructive operation.
+pub async fn confirm_destructive(
+    parent: &impl IsA<gtk::Widget>,
+    heading: &str,
+    body: &str,
+    confirm_label: &str,
+) -> bool {
+    let dialog = adw::AlertDialog::builder()
+        .heading(heading)
+        .body(body)
+        .close_response(RESPONSE_CANCEL)
+        .default_response(RESPONSE_CANCEL)
+        .build();
+    dialog.add_responses(&[
+        (RESPONSE_CANCEL, "Cancel"),
+        (RESPONSE_CONFIRM, confirm_label),
+    ]);
+    dialog.set_response_appearance(RESPONSE_CONFIRM, adw::ResponseAppearance::Destructive);
+
+    dialog.choose_future(parent).await == RESPONSE_CONFIRM
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictResolution {
+    KeepMine,
+    TakeTheirs,
+    Merge,
+}
+
+/// Asks what to do about a note that was changed elsewhere while being
+/// edited. Closing the dialog merges, as that's the only choice that loses
+/// nothing.
+pub async fn resolve_conflict(parent: &impl IsA<gtk::Widget>, name: &str) -> ConflictResolution {
+    let dialog = adw::AlertDialog::builder()
+        .heading(format!("“{}” was changed elsewhere", name))
+        .body("The note was modified on disk since you opened it, e.g. by another editor or a sync tool.")
+        .close_response(RESPONSE_MERGE)
+        .default_response(RESPONSE_MERGE)
+        .build();
+    dialog.add_responses(&[
+        (RESPONSE_THEIRS, "Take Theirs"),
+        (RESPONSE_MERGE, "Show Diff and Merge"),
+        (RESPONSE_MINE, "Keep Mine"),
+    ]);
+    dialog.set_response_appearance(RESPONSE_THEIRS, adw::ResponseAppearance::Destructive);
+    dialog.set_response_appearance(RESPONSE_MERGE, adw::ResponseAppearance::Suggested);
+
+    match dialog.choose_future(parent).await.as_str() {
+        RESPONSE_MINE => ConflictResolution::KeepMine,
+        RESPONSE_THEIRS => ConflictResolution::TakeTheirs,
+        _ => ConflictResolution::Merge,
+    }
+}
+
+/// Asks for another notes folder when the configured one can't be opened.
+/// Shown before the main window exists, hence the parentless GTK dialogs.
+pub async fn choose_notes_folder(error: &Error) -> Option<String> {
+    let alert = gtk::AlertDialog::builder()
+        .message("Cannot open notes folder")
+        .detail(format!(
+            "{}\n\nSet NOTES_ROOTDIR or choose a folder to use.",
+            error
+        ))
+        .buttons(["Quit", "Choose Folder…"])
+        .cancel_button(0)
+        .default_button(1)
+        .build();
+    if !matches!(alert.choose_future(None::<&gtk::Window>).await, Ok(1)) {
+        return None;
+    }
+
+    let file_dialog = gtk::FileDialog::builder()
+        .title("Choose Notes Folder")
+        .build();
+    file_dialog
+        .select_folder_future(None::<&gtk::Window>)
+        .await
+        .ok()
+        .map(|folder| folder.uri().to_string())
+}
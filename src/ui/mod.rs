@@ -1,11 +1,16 @@
 pub mod about_dialog;
 pub mod app;
+pub mod attachment_view;
+pub mod autosave;
+pub mod dialogs;
 pub mod note_editor;
 pub mod note_list_item;
 pub mod note_panel;
+pub mod note_resources;
 pub mod note_view;
 pub mod note_web_view;
 pub mod path_select;
 pub mod path_select_item;
+pub mod search_result_item;
 pub mod sidebar;
 pub mod title;
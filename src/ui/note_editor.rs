@@ -4,16 +4,18 @@ use gtk::{glib, prelude::*};
 use relm4::{Component, ComponentParts, ComponentSender};
 use sourceview5::prelude::*;
 
-use super::app::AppMsg;
+use super::{app::AppMsg, note_view::Highlight};
 
 pub struct NoteEditor {
     buffer: sourceview5::Buffer,
     buffer_changed_signal: glib::SignalHandlerId,
+    search_context: sourceview5::SearchContext,
 }
 
 #[derive(Debug)]
 pub enum NoteEditorMsg {
     SetContent { content: String, name: String },
+    Highlight(Option<Highlight>),
 }
 
 #[relm4::component(pub)]
@@ -28,6 +30,7 @@ impl Component for NoteEditor {
         gtk::ScrolledWindow {
             set_hexpand: true,
             set_vexpand: true,
+            #[name = "source_view"]
             sourceview5::View {
                 set_buffer: Some(&model.buffer),
                 set_monospace: true,
@@ -55,22 +58,49 @@ impl Component for NoteEditor {
             let _ = sender_clone.output(AppMsg::NoteContentChanged(content));
         });
 
+        let search_settings = sourceview5::SearchSettings::builder()
+            .case_sensitive(false)
+            .wrap_around(true)
+            .build();
+        let search_context = sourceview5::SearchContext::new(&buffer, Some(&search_settings));
+        search_context.set_highlight(false);
+
         let model = Self {
             buffer,
             buffer_changed_signal,
+            search_context,
         };
 
         let widgets = view_output!();
 
+        // lets a preview next to the editor follow it
+        let source_view = widgets.source_view.clone();
+        root.vadjustment().connect_value_changed(move |adjustment| {
+            let (iter, _) = source_view.line_at_y(adjustment.value() as i32);
+            let _ = sender.output(AppMsg::EditorScrolled(iter.line() as usize));
+        });
+
         ComponentParts { model, widgets }
     }
 
-    fn update(&mut self, msg: Self::Input, _sender: ComponentSender<Self>, _root: &Self::Root) {
+    fn update_with_view(
+        &mut self,
+        widgets: &mut Self::Widgets,
+        msg: Self::Input,
+        _sender: ComponentSender<Self>,
+        _root: &Self::Root,
+    ) {
         match msg {
             NoteEditorMsg::SetContent { content, name } => {
-                self.buffer.block_signal(&self.buffer_changed_signal);
-                self.buffer.set_text(content.as_str());
-                self.buffer.unblock_signal(&self.buffer_changed_signal);
+                // keeps the cursor where it is when switching modes
+                let current =
+                    self.buffer
+                        .text(&self.buffer.start_iter(), &self.buffer.end_iter(), false);
+                if current != content {
+                    self.buffer.block_signal(&self.buffer_changed_signal);
+                    self.buffer.set_text(content.as_str());
+                    self.buffer.unblock_signal(&self.buffer_changed_signal);
+                }
 
                 self.buffer.set_language(
                     sourceview5::LanguageManager::default()
@@ -78,6 +108,26 @@ impl Component for NoteEditor {
                         .as_ref(),
                 );
             }
+            NoteEditorMsg::Highlight(None) => {
+                self.search_context.set_highlight(false);
+            }
+            NoteEditorMsg::Highlight(Some(highlight)) => {
+                self.search_context
+                    .settings()
+                    .set_search_text(Some(&highlight.query));
+                self.search_context.set_highlight(true);
+
+                if let Some(iter) = self.buffer.iter_at_line(highlight.line_number as i32) {
+                    self.buffer.place_cursor(&iter);
+                    widgets.source_view.scroll_to_mark(
+                        &self.buffer.get_insert(),
+                        0.1,
+                        true,
+                        0.0,
+                        0.3,
+                    );
+                }
+            }
         }
     }
 }
@@ -2,8 +2,8 @@ use crate::{
     icon_names,
     persistence::models::{AnyItem, ItemKind},
 };
-use gtk;
 use gtk::prelude::*;
+use gtk::{self, gdk, glib};
 use relm4::{prelude::*, view};
 
 #[derive(Debug)]
@@ -21,6 +21,28 @@ impl NoteListItem {
         Self { item: any_item }
     }
 
+    /// Extracts the item from a list model entry or a dropped value.
+    pub fn item_from_object(obj: &glib::Object) -> Option<Box<dyn AnyItem>> {
+        obj.downcast_ref::<glib::BoxedAnyObject>()
+            .and_then(|boxed| {
+                boxed
+                    .try_borrow::<NoteListItem>()
+                    .ok()
+                    .map(|note_list_item| note_list_item.item.clone())
+            })
+    }
+
+    pub fn item_from_value(value: &glib::Value) -> Option<Box<dyn AnyItem>> {
+        value
+            .get::<glib::Object>()
+            .ok()
+            .and_then(|obj| Self::item_from_object(&obj))
+    }
+
+    pub fn drop_target() -> gtk::DropTarget {
+        gtk::DropTarget::new(glib::BoxedAnyObject::static_type(), gdk::DragAction::MOVE)
+    }
+
     pub fn setup(_list_item: &gtk::ListItem) -> (gtk::Box, NoteListItemWidgets) {
         view! {
             root = gtk::Box {
@@ -3,6 +3,7 @@ use relm4::Component;
 use relm4::prelude::*;
 
 use crate::icon_names;
+use crate::persistence::frontmatter::Metadata;
 use crate::ui::note_view::Mode;
 
 use super::app::AppMsg;
@@ -13,6 +14,8 @@ pub struct NotePanel {
 
 #[derive(Debug)]
 pub enum NotePanelMsg {
+    ApplyMetadata,
+    SetMetadata(Metadata),
     SetMode(Mode),
 }
 
@@ -25,56 +28,124 @@ impl Component for NotePanel {
 
     view! {
         #[root]
-        gtk::Box{
+        gtk::Box {
             set_orientation: gtk::Orientation::Horizontal,
-            set_homogeneous: true,
-            add_css_class: "linked",
+            set_spacing: 6,
 
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_homogeneous: true,
+                add_css_class: "linked",
 
-            #[name="view_toggle_button"]
-            gtk::ToggleButton {
-                set_can_focus: false,
-                #[watch]
-                set_active: model.mode == Mode::View,
-                connect_toggled[sender] => move |btn| {
-                    if btn.is_active() {
-                        let _ = sender.output(AppMsg::SetMode(Mode::View));
+
+                #[name="view_toggle_button"]
+                gtk::ToggleButton {
+                    set_can_focus: false,
+                    #[watch]
+                    set_active: model.mode == Mode::View,
+                    connect_toggled[sender] => move |btn| {
+                        if btn.is_active() {
+                            let _ = sender.output(AppMsg::SetMode(Mode::View));
+                        }
+                    },
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 8,
+                        gtk::Image {
+                            set_icon_name: Some(icon_names::DOCUMENT_ONE_PAGE_REGULAR)
+                        },
+                        gtk::Label {
+                            set_label: "View",
+                        }
                     }
                 },
+                gtk::ToggleButton {
+                    set_can_focus: false,
+                    #[watch]
+                    set_active: model.mode == Mode::Edit,
+                    set_group: Some(&view_toggle_button),
+                    connect_toggled[sender] => move |btn| {
+                        if btn.is_active() {
+                            let _ = sender.output(AppMsg::SetMode(Mode::Edit));
+                        }
+                    },
 
-                gtk::Box {
-                    set_orientation: gtk::Orientation::Horizontal,
-                    set_spacing: 8,
-                    gtk::Image {
-                        set_icon_name: Some(icon_names::DOCUMENT_ONE_PAGE_REGULAR)
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 8,
+                        gtk::Image {
+                            set_icon_name: Some(icon_names::EDIT_REGULAR),
+                            set_margin_end: 4,
+                        },
+                        gtk::Label {
+                            set_label: "Edit",
+                        }
+                    }
+                },
+                gtk::ToggleButton {
+                    set_can_focus: false,
+                    #[watch]
+                    set_active: model.mode == Mode::Split,
+                    set_group: Some(&view_toggle_button),
+                    connect_toggled[sender] => move |btn| {
+                        if btn.is_active() {
+                            let _ = sender.output(AppMsg::SetMode(Mode::Split));
+                        }
                     },
-                    gtk::Label {
-                        set_label: "View",
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_spacing: 8,
+                        gtk::Image {
+                            set_icon_name: Some(icon_names::LAYOUT_COLUMN_TWO_REGULAR),
+                            set_margin_end: 4,
+                        },
+                        gtk::Label {
+                            set_label: "Split",
+                        }
                     }
-                }
+                },
             },
-            gtk::ToggleButton {
+
+            #[name = "properties_button"]
+            gtk::MenuButton {
                 set_can_focus: false,
-                #[watch]
-                set_active: model.mode == Mode::Edit,
-                set_group: Some(&view_toggle_button),
-                connect_toggled[sender] => move |btn| {
-                    if btn.is_active() {
-                        let _ = sender.output(AppMsg::SetMode(Mode::Edit));
-                    }
-                },
+                set_icon_name: icon_names::TAG_REGULAR,
+                set_tooltip_text: Some("Title and tags"),
+
+                #[wrap(Some)]
+                set_popover = &gtk::Popover {
+                    gtk::Grid {
+                        set_row_spacing: 6,
+                        set_column_spacing: 12,
 
-                gtk::Box {
-                    set_orientation: gtk::Orientation::Horizontal,
-                    set_spacing: 8,
-                    gtk::Image {
-                        set_icon_name: Some(icon_names::EDIT_REGULAR),
-                        set_margin_end: 4,
+                        attach[0, 0, 1, 1] = &gtk::Label {
+                            set_label: "Title",
+                            set_halign: gtk::Align::End,
+                        },
+                        #[name = "title_entry"]
+                        attach[1, 0, 1, 1] = &gtk::Entry {
+                            set_width_chars: 30,
+                            connect_activate => NotePanelMsg::ApplyMetadata,
+                        },
+                        attach[0, 1, 1, 1] = &gtk::Label {
+                            set_label: "Tags",
+                            set_halign: gtk::Align::End,
+                        },
+                        #[name = "tags_entry"]
+                        attach[1, 1, 1, 1] = &gtk::Entry {
+                            set_placeholder_text: Some("comma separated"),
+                            connect_activate => NotePanelMsg::ApplyMetadata,
+                        },
+                        attach[1, 2, 1, 1] = &gtk::Button {
+                            set_label: "Apply",
+                            set_halign: gtk::Align::End,
+                            add_css_class: "suggested-action",
+                            connect_clicked => NotePanelMsg::ApplyMetadata,
+                        },
                     },
-                    gtk::Label {
-                        set_label: "Edit",
-                    }
-                }
+                },
             },
         }
     }
@@ -91,16 +162,41 @@ impl Component for NotePanel {
         ComponentParts { model, widgets }
     }
 
-    fn update(
+    fn update_with_view(
         &mut self,
+        widgets: &mut Self::Widgets,
         msg: Self::Input,
-        _sender: ComponentSender<NotePanel>,
+        sender: ComponentSender<NotePanel>,
         _root: &Self::Root,
     ) {
         match msg {
+            NotePanelMsg::ApplyMetadata => {
+                let title = widgets.title_entry.text().trim().to_string();
+                let tags = widgets
+                    .tags_entry
+                    .text()
+                    .split(',')
+                    .map(|tag| tag.trim().trim_start_matches('#').to_string())
+                    .filter(|tag| !tag.is_empty())
+                    .collect();
+
+                widgets.properties_button.popdown();
+                let _ = sender.output(AppMsg::EditMetadata {
+                    title: (!title.is_empty()).then_some(title),
+                    tags,
+                });
+            }
+            NotePanelMsg::SetMetadata(metadata) => {
+                widgets
+                    .title_entry
+                    .set_text(metadata.title.as_deref().unwrap_or_default());
+                widgets.tags_entry.set_text(&metadata.tags.join(", "));
+            }
             NotePanelMsg::SetMode(mode) => {
                 self.mode = mode;
             }
         }
+
+        self.update_view(widgets, sender);
     }
 }
@@ -0,0 +1,116 @@
+use std::rc::Rc;
+
+use gtk::{gio, glib, prelude::*};
+
+use crate::LOG_DOMAIN;
+use crate::errors::Error;
+use crate::persistence::models::ItemKind;
+use crate::persistence::storage::{self, ItemStorage};
+use crate::util::markdown::escape_html;
+
+/// Scheme notes are shown at, so that images and other files next to them
+/// are loaded through the storage, whatever its backend.
+pub const RESOURCE_SCHEME: &str = "note-resource";
+
+/// Lets web views load `note-resource:` URIs from `storage`. Anything that
+/// can't be loaded is replaced by a placeholder image.
+pub fn register_resource_scheme(storage: Rc<dyn ItemStorage>) {
+    let Some(context) = webkit6::WebContext::default() else {
+        return;
+    };
+
+    context.register_uri_scheme(RESOURCE_SCHEME, move |request| {
+        let storage = storage.clone();
+        let request = request.clone();
+
+        glib::spawn_future_local(async move {
+            let names = request
+                .uri()
+                .and_then(|uri| resource_names(&uri))
+                .unwrap_or_default();
+            let (bytes, mime_type) = match load(storage.as_ref(), &names).await {
+                Ok(Some(resource)) => resource,
+                Ok(None) => placeholder(names.last()),
+                Err(error) => {
+                    glib::g_warning!(LOG_DOMAIN, "{}", error);
+                    placeholder(names.last())
+                }
+            };
+
+            let length = bytes.len() as i64;
+            let stream = gio::MemoryInputStream::from_bytes(&glib::Bytes::from_owned(bytes));
+            request.finish(&stream, length, Some(&mime_type));
+        });
+    });
+}
+
+/// Returns the URI a note is shown at, for resolving its relative links.
+pub fn base_uri(root_location: &str, note_location: &str) -> Option<String> {
+    let relative_path =
+        gio::File::for_uri(root_location).relative_path(&gio::File::for_uri(note_location))?;
+    let path = relative_path
+        .iter()
+        .map(|name| glib::Uri::escape_string(&name.to_string_lossy(), None, false).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+
+    Some(format!("{}:///{}", RESOURCE_SCHEME, path))
+}
+
+/// Returns the names leading from the root collection to the item a
+/// `note-resource:` URI points to, or `None` for other URIs.
+pub fn resource_names(uri: &str) -> Option<Vec<String>> {
+    let path = uri
+        .strip_prefix(RESOURCE_SCHEME)?
+        .strip_prefix(':')?
+        .split(['#', '?'])
+        .next()
+        .unwrap_or_default();
+
+    let names = path
+        .split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .map(|name| {
+            glib::Uri::unescape_string(name, None::<&str>)
+                .map_or(name.to_string(), |name| name.to_string())
+        })
+        .collect();
+
+    Some(names)
+}
+
+async fn load(
+    storage: &dyn ItemStorage,
+    names: &[String],
+) -> Result<Option<(Vec<u8>, String)>, Error> {
+    let Some((_, item)) = storage::find_item(storage, names).await? else {
+        return Ok(None);
+    };
+    if item.kind() == ItemKind::Collection {
+        return Ok(None);
+    }
+
+    let bytes = storage.load_bytes(item.as_ref()).await?;
+    let (content_type, _) = gio::content_type_guess(Some(item.name()), &bytes);
+    let mime_type = gio::content_type_get_mime_type(&content_type)
+        .map_or("application/octet-stream".to_string(), |mime_type| {
+            mime_type.to_string()
+        });
+
+    Ok(Some((bytes, mime_type)))
+}
+
+/// An image standing in for a missing attachment, showing its name.
+fn placeholder(name: Option<&String>) -> (Vec<u8>, String) {
+    let name = name.map_or("", |name| name.as_str());
+    let svg = format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="240" height="64" viewBox="0 0 240 64">
+<rect x="1" y="1" width="238" height="62" rx="6" fill="#f6f5f4" stroke="#c01c28" stroke-dasharray="4 3"/>
+<text x="120" y="28" font-family="sans-serif" font-size="12" fill="#c01c28" text-anchor="middle">Missing attachment</text>
+<text x="120" y="46" font-family="sans-serif" font-size="11" fill="#5e5c64" text-anchor="middle">{}</text>
+</svg>"##,
+        escape_html(name)
+    );
+
+    (svg.into_bytes(), "image/svg+xml".to_string())
+}
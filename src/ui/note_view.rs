@@ -1,10 +1,24 @@
-use std::convert::identity;
+use std::{convert::identity, time::Duration};
 
 use crate::{
-    persistence::models::AnyNote,
-    ui::{note_panel::NotePanel, note_web_view::NoteWebView},
+    persistence::{
+        frontmatter::{self, Frontmatter, Metadata, Value},
+        models::{AnyAttachment, AnyNote},
+        storage::{AttachmentInfo, SearchMatch},
+    },
+    ui::{
+        attachment_view::{AttachmentView, AttachmentViewMsg, Preview},
+        note_panel::NotePanel,
+        note_resources,
+        note_web_view::NoteWebView,
+        search_result_item::SearchResultItem,
+    },
+    util::{
+        markdown::{self, MarkdownOptions},
+        wiki::WikiTargets,
+    },
 };
-use gtk::prelude::*;
+use gtk::{gio, glib, prelude::*};
 use relm4::{Controller, prelude::*};
 
 use super::{
@@ -14,13 +28,39 @@ use super::{
     note_web_view::NoteWebViewMsg,
 };
 
+/// How long typing has to pause before the rendered note is updated in split
+/// mode, so that it isn't rendered again for every key.
+const PREVIEW_DELAY: Duration = Duration::from_millis(300);
+
+/// A search match to point out in the current note.
+#[derive(Debug, Clone)]
+pub struct Highlight {
+    pub query: String,
+    pub line_number: usize,
+}
+
 pub struct NoteView {
+    /// location of the root collection, for resolving relative links
+    root_location: String,
     note: Option<Box<dyn AnyNote>>,
     content: Option<String>,
+    metadata: Metadata,
+    highlight: Option<Highlight>,
     mode: Mode,
+    /// bumped on every change, so that earlier preview timeouts can be told
+    /// apart
+    preview_generation: u64,
+    /// notes linking to the current one
+    backlinks_store: gio::ListStore,
+    backlinks_model: gtk::SingleSelection,
+    backlink_count: u32,
+    show_backlinks: bool,
+    /// whether an attachment is shown instead of a note
+    showing_attachment: bool,
     panel: Controller<NotePanel>,
     web_view: Controller<NoteWebView>,
     editor: Controller<NoteEditor>,
+    attachment_view: Controller<AttachmentView>,
 }
 
 impl NoteView {
@@ -28,17 +68,56 @@ impl NoteView {
         self.mode = mode;
 
         if let Some(content) = self.content.clone() {
-            match &self.mode {
-                Mode::Edit => {
-                    self.editor.emit(NoteEditorMsg::SetContent {
-                        content,
-                        name: self.note.as_ref().unwrap().name(),
-                    });
-                }
-                Mode::View => {
-                    self.web_view.emit(NoteWebViewMsg::ChangeContent(content));
-                }
+            if self.mode != Mode::View {
+                self.editor.emit(NoteEditorMsg::SetContent {
+                    content: content.clone(),
+                    name: self.note.as_ref().unwrap().name(),
+                });
+            }
+            if self.mode != Mode::Edit {
+                self.web_view.emit(NoteWebViewMsg::ChangeContent(content));
             }
+            if self.mode == Mode::View {
+                self.web_view.emit(NoteWebViewMsg::Focus());
+            }
+        }
+
+        self.show_highlight();
+    }
+
+    /// Shows the frontmatter of the current content in the panel.
+    fn update_metadata(&mut self) {
+        let metadata = self
+            .content
+            .as_deref()
+            .and_then(Frontmatter::parse)
+            .map(|frontmatter| frontmatter.metadata)
+            .unwrap_or_default();
+
+        if metadata != self.metadata {
+            self.metadata = metadata.clone();
+            self.panel.emit(NotePanelMsg::SetMetadata(metadata));
+        }
+    }
+
+    fn hide_attachment(&mut self) {
+        if self.showing_attachment {
+            self.showing_attachment = false;
+            self.attachment_view.emit(AttachmentViewMsg::Clear());
+        }
+    }
+
+    fn show_highlight(&self) {
+        if self.mode != Mode::View {
+            self.editor
+                .emit(NoteEditorMsg::Highlight(self.highlight.clone()));
+        }
+        if self.mode != Mode::Edit {
+            self.web_view.emit(NoteWebViewMsg::Highlight(
+                self.highlight
+                    .as_ref()
+                    .map(|highlight| highlight.query.clone()),
+            ));
         }
     }
 }
@@ -47,13 +126,17 @@ impl NoteView {
 pub enum Mode {
     Edit,
     View,
+    /// editor and rendered note side by side
+    Split,
 }
 
 impl Mode {
+    /// The mode to switch to next, going from viewing to editing to both.
     pub fn toggled(&self) -> Mode {
         match self {
-            Mode::Edit => Mode::View,
             Mode::View => Mode::Edit,
+            Mode::Edit => Mode::Split,
+            Mode::Split => Mode::View,
         }
     }
 }
@@ -61,16 +144,37 @@ impl Mode {
 #[derive(Debug)]
 pub enum NoteViewMsg {
     ContentChanged(String),
+    EditMetadata {
+        title: Option<String>,
+        tags: Vec<String>,
+    },
+    EditorScrolled(usize),
+    Highlight(Highlight),
+    LoadedAttachment {
+        attachment: Box<dyn AnyAttachment>,
+        info: AttachmentInfo,
+        preview: Preview,
+    },
     LoadedNote {
         note: Box<dyn AnyNote>,
         content: String,
     },
+    PreviewTimedOut(u64),
+    RelocatedNote(Box<dyn AnyNote>),
+    ReloadedContent(String),
+    SelectedBacklink(u32),
+    SetBacklinks(Vec<SearchMatch>),
     SetMode(Mode),
+    SetMarkdownOptions(MarkdownOptions),
+    SetWikiTargets(WikiTargets),
+    ShowBacklinks(bool),
+    ToggleTask(usize),
+    UnloadedNote(),
 }
 
 #[relm4::component(pub, async)]
 impl AsyncComponent for NoteView {
-    type Init = ();
+    type Init = String;
     type Input = NoteViewMsg;
     type Output = AppMsg;
     type CommandOutput = ();
@@ -83,35 +187,112 @@ impl AsyncComponent for NoteView {
                 Some(_) => gtk::Box {
                     set_orientation: gtk::Orientation::Horizontal,
                     model.panel.widget(),
+
+                    gtk::ToggleButton {
+                        set_hexpand: true,
+                        set_halign: gtk::Align::End,
+                        set_can_focus: false,
+                        set_tooltip_text: Some("Notes linking here"),
+                        #[watch]
+                        set_label: &format!("Backlinks ({})", model.backlink_count),
+                        #[watch]
+                        set_active: model.show_backlinks,
+                        connect_toggled[sender] => move |button| {
+                            sender.input(NoteViewMsg::ShowBacklinks(button.is_active()));
+                        },
+                    },
                 },
                 _ => gtk::Box {}
             },
 
-            gtk::Stack {
-                add_child = &gtk::Box {
-                    set_hexpand: true,
-                    append: model.web_view.widget()
-                } -> { set_name: "view" },
-                add_child = &gtk::Box {
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+
+                gtk::Stack {
                     set_hexpand: true,
-                    append: model.editor.widget()
-                } -> { set_name: "edit" },
-                add_child = &gtk::Label {
-                    set_label: "no note loaded",
-                } -> { set_name: "none" },
-
-                #[watch]
-                set_visible_child_name: match (&model.mode, &model.content) {
-                    (_, None) => "none",
-                    (Mode::View, _) => "view",
-                    (Mode::Edit, _) => "edit"
+
+                    add_child = &gtk::Paned {
+                        set_orientation: gtk::Orientation::Horizontal,
+                        set_shrink_start_child: false,
+                        set_shrink_end_child: false,
+
+                        #[wrap(Some)]
+                        set_start_child = &gtk::Box {
+                            set_hexpand: true,
+                            #[watch]
+                            set_visible: model.mode != Mode::View,
+                            append: model.editor.widget()
+                        },
+                        #[wrap(Some)]
+                        set_end_child = &gtk::Box {
+                            set_hexpand: true,
+                            #[watch]
+                            set_visible: model.mode != Mode::Edit,
+                            append: model.web_view.widget()
+                        },
+                    } -> { set_name: "note" },
+                    add_child = &gtk::Box {
+                        set_hexpand: true,
+                        append: model.attachment_view.widget()
+                    } -> { set_name: "attachment" },
+                    add_child = &gtk::Label {
+                        set_label: "no note loaded",
+                    } -> { set_name: "none" },
+
+                    #[watch]
+                    set_visible_child_name: match &model.content {
+                        _ if model.showing_attachment => "attachment",
+                        None => "none",
+                        Some(_) => "note",
+                    },
+                },
+
+                gtk::Revealer {
+                    set_transition_type: gtk::RevealerTransitionType::SlideLeft,
+                    #[watch]
+                    set_reveal_child: model.show_backlinks && model.content.is_some(),
+
+                    gtk::Box {
+                        set_orientation: gtk::Orientation::Vertical,
+                        set_width_request: 240,
+
+                        gtk::Label {
+                            set_label: "Backlinks",
+                            set_halign: gtk::Align::Start,
+                            set_margin_all: 8,
+                            add_css_class: "heading",
+                        },
+                        gtk::Stack {
+                            add_child = &gtk::ScrolledWindow {
+                                set_vexpand: true,
+
+                                gtk::ListView {
+                                    set_factory: Some(&SearchResultItem::factory()),
+                                    set_model: Some(&model.backlinks_model),
+                                    set_single_click_activate: true,
+
+                                    connect_activate[sender] => move |_, index| {
+                                        sender.input(NoteViewMsg::SelectedBacklink(index));
+                                    }
+                                },
+                            } -> { set_name: "backlinks" },
+                            add_child = &gtk::Label {
+                                set_label: "No notes link here",
+                                set_valign: gtk::Align::Start,
+                                add_css_class: "dim-label",
+                            } -> { set_name: "none" },
+
+                            #[watch]
+                            set_visible_child_name: if model.backlink_count == 0 { "none" } else { "backlinks" },
+                        },
+                    },
                 },
             },
         }
     }
 
     async fn init(
-        _: Self::Init,
+        root_location: Self::Init,
         root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
@@ -124,13 +305,32 @@ impl AsyncComponent for NoteView {
         let editor: Controller<NoteEditor> = NoteEditor::builder()
             .launch(String::from(""))
             .forward(sender.output_sender(), identity);
+        let attachment_view: Controller<AttachmentView> = AttachmentView::builder()
+            .launch(())
+            .forward(sender.output_sender(), identity);
+        let backlinks_store = gio::ListStore::builder()
+            .item_type(glib::BoxedAnyObject::static_type())
+            .build();
+        let backlinks_model = gtk::SingleSelection::builder()
+            .model(&backlinks_store)
+            .build();
         let model = NoteView {
+            root_location,
             note: None,
             content: None,
+            metadata: Metadata::default(),
+            highlight: None,
+            backlinks_store,
+            backlinks_model,
+            backlink_count: 0,
+            show_backlinks: false,
+            showing_attachment: false,
             panel,
             web_view,
             editor,
+            attachment_view,
             mode: Mode::View,
+            preview_generation: 0,
         };
 
         let widgets = view_output!();
@@ -147,6 +347,15 @@ impl AsyncComponent for NoteView {
         match msg {
             NoteViewMsg::ContentChanged(content) => {
                 self.content = Some(content.clone());
+                self.update_metadata();
+                if self.mode == Mode::Split {
+                    self.preview_generation += 1;
+                    let generation = self.preview_generation;
+                    let sender = sender.clone();
+                    glib::timeout_add_local_once(PREVIEW_DELAY, move || {
+                        sender.input(NoteViewMsg::PreviewTimedOut(generation));
+                    });
+                }
                 let _ = sender.output(AppMsg::ContentChanged {
                     note: self.note.clone().unwrap().clone(),
                     content,
@@ -166,19 +375,162 @@ impl AsyncComponent for NoteView {
                 //         |etag| Some(etag),
                 //     );
             }
+            NoteViewMsg::EditMetadata { title, tags } => {
+                let Some(original) = self.content.clone() else {
+                    return;
+                };
+
+                // only touch fields that changed, so the rest stays as written
+                let mut content = original.clone();
+                if title != self.metadata.title {
+                    content =
+                        frontmatter::set_field(&content, "title", title.map(Value::Text).as_ref());
+                }
+                if tags != self.metadata.tags {
+                    content = frontmatter::set_field(
+                        &content,
+                        "tags",
+                        (!tags.is_empty()).then_some(Value::List(tags)).as_ref(),
+                    );
+                }
+
+                if content != original {
+                    self.content = Some(content.clone());
+                    self.update_metadata();
+                    self.set_mode(self.mode.clone());
+                    let _ = sender.output(AppMsg::ContentChanged {
+                        note: self.note.clone().unwrap(),
+                        content,
+                    });
+                }
+            }
+            NoteViewMsg::ToggleTask(offset) => {
+                let Some(content) = self
+                    .content
+                    .as_ref()
+                    .and_then(|content| markdown::toggle_task(content, offset))
+                else {
+                    // rendered from an older version of the note
+                    return;
+                };
+
+                self.content = Some(content.clone());
+                self.set_mode(self.mode.clone());
+                let _ = sender.output(AppMsg::ContentChanged {
+                    note: self.note.clone().unwrap(),
+                    content,
+                });
+            }
             NoteViewMsg::SetMode(mode) => {
                 self.set_mode(mode);
                 self.panel
                     .sender()
                     .emit(NotePanelMsg::SetMode(self.mode.clone()));
             }
+            NoteViewMsg::LoadedAttachment {
+                attachment,
+                info,
+                preview,
+            } => {
+                self.web_view.emit(NoteWebViewMsg::SetLocation(None));
+                self.note = None;
+                self.content = None;
+                self.showing_attachment = true;
+                self.attachment_view.emit(AttachmentViewMsg::Show {
+                    attachment,
+                    info,
+                    preview,
+                });
+            }
             NoteViewMsg::LoadedNote { note, content } => {
+                self.hide_attachment();
+                self.web_view
+                    .emit(NoteWebViewMsg::SetLocation(note_resources::base_uri(
+                        &self.root_location,
+                        &note.location(),
+                    )));
                 self.note = Some(note);
                 self.content = Some(content);
-                self.mode = Mode::View;
+                self.highlight = None;
+                self.update_metadata();
+                self.backlinks_store.remove_all();
+                self.backlink_count = 0;
 
-                let content = self.content.clone().unwrap();
-                self.web_view.emit(NoteWebViewMsg::ChangeContent(content));
+                // stays in the mode the app and the panel know about
+                self.set_mode(self.mode.clone());
+            }
+            NoteViewMsg::PreviewTimedOut(generation) => {
+                if generation == self.preview_generation
+                    && self.mode == Mode::Split
+                    && let Some(content) = self.content.clone()
+                {
+                    self.web_view.emit(NoteWebViewMsg::ChangeContent(content));
+                }
+            }
+            NoteViewMsg::EditorScrolled(line) => {
+                if self.mode == Mode::Split {
+                    self.web_view.emit(NoteWebViewMsg::ScrollToLine(line));
+                }
+            }
+            NoteViewMsg::Highlight(highlight) => {
+                self.highlight = Some(highlight);
+                self.show_highlight();
+            }
+            NoteViewMsg::RelocatedNote(note) => {
+                self.web_view
+                    .emit(NoteWebViewMsg::SetLocation(note_resources::base_uri(
+                        &self.root_location,
+                        &note.location(),
+                    )));
+                self.note = Some(note);
+            }
+            NoteViewMsg::ReloadedContent(content) => {
+                self.content = Some(content);
+                self.update_metadata();
+                self.set_mode(self.mode.clone());
+            }
+            NoteViewMsg::SetBacklinks(backlinks) => {
+                self.backlinks_store.remove_all();
+                self.backlinks_store
+                    .extend(backlinks.into_iter().map(|search_match| {
+                        glib::BoxedAnyObject::new(SearchResultItem::from_search_match(search_match))
+                    }));
+                self.backlink_count = self.backlinks_store.n_items();
+            }
+            NoteViewMsg::SelectedBacklink(index) => {
+                let search_match = self
+                    .backlinks_model
+                    .item(index)
+                    .and_then(|obj| SearchResultItem::search_match_from_object(&obj));
+                if let (Some(search_match), Some(note)) = (search_match, &self.note) {
+                    // point out where the current note is mentioned
+                    let name = note.name();
+                    let query = name
+                        .rsplit_once('.')
+                        .map_or(name.as_str(), |(stem, _)| stem)
+                        .to_string();
+                    let _ = sender.output(AppMsg::OpenSearchResult {
+                        search_match,
+                        query,
+                    });
+                }
+            }
+            NoteViewMsg::ShowBacklinks(show_backlinks) => {
+                self.show_backlinks = show_backlinks;
+            }
+            NoteViewMsg::SetMarkdownOptions(markdown_options) => {
+                self.web_view
+                    .emit(NoteWebViewMsg::SetMarkdownOptions(markdown_options));
+            }
+            NoteViewMsg::SetWikiTargets(wiki_targets) => {
+                self.web_view
+                    .emit(NoteWebViewMsg::SetWikiTargets(wiki_targets));
+            }
+            NoteViewMsg::UnloadedNote() => {
+                self.hide_attachment();
+                self.web_view.emit(NoteWebViewMsg::SetLocation(None));
+                self.note = None;
+                self.content = None;
             }
         }
     }
@@ -1,19 +1,408 @@
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
+
+use gtk::{gdk, gio, glib};
 use relm4::{Component, ComponentParts, ComponentSender};
-use webkit6::{NavigationPolicyDecision, PolicyDecisionType, prelude::*};
+use webkit6::{NavigationPolicyDecision, NavigationType, PolicyDecisionType, prelude::*};
+
+use crate::{
+    LOG_DOMAIN,
+    ui::{app::AppMsg, note_resources::RESOURCE_SCHEME},
+    util::{
+        diagrams::{MERMAID_SCRIPT, Mermaid, diagram_program, draw_diagram},
+        highlight::Highlighter,
+        markdown::{CodeBlockRenderer, MarkdownOptions, WIKI_LINK_SCHEME, markdown_to_html},
+        wiki::WikiTargets,
+    },
+};
+
+/// Name of the script message handler told about ticked task list items.
+const TOGGLE_TASK_HANDLER: &str = "toggleTask";
+/// Name of the script message handler given code to copy to the clipboard.
+const COPY_CODE_HANDLER: &str = "copyCode";
+
+/// KaTeX, typesetting the math in notes without needing the network.
+const KATEX_SCRIPT: &str = include_str!("../../resources/katex/katex.min.js");
+
+/// Scripts only some notes need, along with what the rendered HTML of those
+/// notes contains. They're only added to pages that need them.
+const ON_DEMAND_SCRIPTS: [(&str, &str); 2] = [
+    ("class=\"language-math", KATEX_SCRIPT),
+    ("class=\"diagram mermaid\"", MERMAID_SCRIPT),
+];
+
+/// Updates the page in place, so that it keeps its scroll position, scrolls
+/// to blocks by the line they start on, typesets math, draws Mermaid diagrams
+/// and reports clicked task checkboxes and copy buttons.
+const SCRIPT: &str = "
+// blocks as rendered by the app, before typesetting their math
+const sources = new WeakMap();
+
+document.addEventListener('DOMContentLoaded', () => {
+    document.body.childNodes.forEach(renderBlock);
+});
+
+function renderBlock(block) {
+    if (block.nodeType !== Node.ELEMENT_NODE) {
+        return;
+    }
+    sources.set(block, block.cloneNode(true));
+    block.querySelectorAll('code.math-inline, code.math-display').forEach(renderMath);
+    block.querySelectorAll('figure.mermaid').forEach(renderMermaid);
+}
+
+let mermaidCount = 0;
+
+// errors are shown below the diagram's source
+function renderMermaid(figure) {
+    mermaid.initialize({
+        startOnLoad: false,
+        securityLevel: 'strict',
+        theme: figure.dataset.dark === 'true' ? 'dark' : 'default',
+    });
+    const source = figure.textContent;
+    mermaidCount += 1;
+    const id = 'mermaid-' + mermaidCount;
+    try {
+        figure.innerHTML = mermaid.render(id, source);
+    } catch (error) {
+        // Mermaid leaves the element it drew into behind when it fails
+        document.getElementById('d' + id)?.remove();
+        const message = document.createElement('div');
+        message.className = 'error';
+        message.textContent = error.message;
+        figure.replaceChildren(Object.assign(document.createElement('pre'), { textContent: source }), message);
+    }
+}
 
-use crate::{ui::app::AppMsg, util::markdown::markdown_to_html};
+// errors are shown in place of the formula
+function renderMath(code) {
+    const display = code.classList.contains('math-display');
+    const math = document.createElement(display ? 'div' : 'span');
+    math.className = display ? 'math display' : 'math';
+    try {
+        katex.render(code.textContent, math, {
+            displayMode: display,
+            output: 'mathml',
+            throwOnError: false,
+            errorColor: '#c01c28',
+        });
+    } catch (error) {
+        math.classList.add('error');
+        math.textContent = error.message;
+    }
+    code.replaceWith(math);
+}
+
+document.addEventListener('click', (event) => {
+    const button = event.target.closest('button.copy-code');
+    if (button) {
+        window.webkit.messageHandlers.copyCode.postMessage(button.nextElementSibling.textContent);
+        button.textContent = 'Copied';
+        setTimeout(() => button.textContent = 'Copy', 1500);
+    }
+});
+
+// the checkbox changes once the note is saved and shown again
+document.addEventListener('click', (event) => {
+    if (event.target.matches('input[type=checkbox][data-offset]')) {
+        event.preventDefault();
+        window.webkit.messageHandlers.toggleTask.postMessage(Number(event.target.dataset.offset));
+    }
+});
+
+function updateBlocks(html) {
+    const template = document.createElement('template');
+    template.innerHTML = html;
+    const blocks = Array.from(template.content.childNodes);
+    const scrollY = window.scrollY;
+
+    blocks.forEach((block, index) => {
+        const old = document.body.childNodes[index];
+        const source = old && (sources.get(old) || old);
+        if (!old) {
+            document.body.appendChild(block);
+            renderBlock(block);
+        } else if (isMovedBlock(source, block)) {
+            old.setAttribute('data-line', block.getAttribute('data-line'));
+            source.setAttribute('data-line', block.getAttribute('data-line'));
+        } else if (!source.isEqualNode(block)) {
+            document.body.replaceChild(block, old);
+            renderBlock(block);
+        }
+    });
+    while (document.body.childNodes.length > blocks.length) {
+        document.body.removeChild(document.body.lastChild);
+    }
+
+    window.scrollTo(0, scrollY);
+}
+
+function isMovedBlock(old, block) {
+    if (old.nodeType !== Node.ELEMENT_NODE || block.nodeType !== Node.ELEMENT_NODE
+        || !old.hasAttribute('data-line') || !block.hasAttribute('data-line')) {
+        return false;
+    }
+    const moved = block.cloneNode(true);
+    moved.setAttribute('data-line', old.getAttribute('data-line'));
+    return old.isEqualNode(moved);
+}
+
+function scrollToLine(line) {
+    let previous = null;
+    let following = null;
+    for (const block of document.querySelectorAll('body > [data-line]')) {
+        if (Number(block.dataset.line) <= line) {
+            previous = block;
+        } else {
+            following = block;
+            break;
+        }
+    }
+    if (!previous) {
+        window.scrollTo(0, 0);
+        return;
+    }
+
+    const top = previous.getBoundingClientRect().top + window.scrollY;
+    let y = top;
+    if (following) {
+        const start = Number(previous.dataset.line);
+        const end = Number(following.dataset.line);
+        const followingTop = following.getBoundingClientRect().top + window.scrollY;
+        y += (followingTop - top) * (line - start) / (end - start);
+    }
+    window.scrollTo(0, y);
+}
+";
 
-#[tracker::track]
 pub struct NoteWebView {
     content: String,
+    /// URI the note is shown at, relative links are resolved against it
+    location: Option<String>,
+    /// notes that wiki links can point to
+    wiki_targets: WikiTargets,
+    /// extensions of the notebook
+    markdown_options: MarkdownOptions,
+    /// whether code and diagrams are rendered for a dark theme
+    dark: bool,
+    /// code blocks as rendered the last time, by language and code
+    rendered_code: RefCell<HashMap<(String, String), Option<String>>>,
+    /// diagrams being drawn in the background, by language and code
+    drawing: RefCell<HashSet<(String, String)>>,
+    /// on-demand scripts added to the page, by what needs them
+    page_scripts: Vec<&'static str>,
+    web_view: webkit6::WebView,
+    /// whether the page has been loaded and can be updated in place
+    loaded: bool,
+    /// text to find once the content is loaded
+    highlight: Option<String>,
+    /// line of the note to scroll to once the content is loaded
+    scroll_line: Option<usize>,
 }
 
 #[derive(Debug)]
 pub enum NoteWebViewMsg {
     ChangeContent(String),
+    DiagramDrawn {
+        language: String,
+        code: String,
+        html: Option<String>,
+    },
+    Focus(),
+    Highlight(Option<String>),
+    LoadFinished(),
+    ScrollToLine(usize),
+    SetDark(bool),
+    SetLocation(Option<String>),
+    SetMarkdownOptions(MarkdownOptions),
+    SetWikiTargets(WikiTargets),
 }
 
-impl NoteWebView {}
+impl NoteWebView {
+    /// Loads the page from scratch, e.g. for another note.
+    fn load(&mut self, sender: &ComponentSender<Self>) {
+        self.loaded = false;
+        let html = self.render(sender);
+
+        let user_content_manager = self.web_view.user_content_manager().unwrap();
+        user_content_manager.remove_all_scripts();
+        self.page_scripts.clear();
+        for script in self.missing_scripts(&html) {
+            user_content_manager.add_script(&user_script(script));
+        }
+        user_content_manager.add_script(&user_script(SCRIPT));
+
+        self.web_view.load_html(&html, self.location.as_deref());
+    }
+
+    /// Returns the on-demand scripts `html` needs that the page doesn't have
+    /// yet, taking them as added.
+    fn missing_scripts(&mut self, html: &str) -> Vec<&'static str> {
+        let mut missing = Vec::new();
+        for (needed_by, script) in ON_DEMAND_SCRIPTS {
+            if html.contains(needed_by) && !self.page_scripts.contains(&needed_by) {
+                self.page_scripts.push(needed_by);
+                missing.push(script);
+            }
+        }
+
+        missing
+    }
+
+    /// Renders the content, rendering only code blocks that changed since the
+    /// last time. Diagrams are shown as highlighted code until they're drawn,
+    /// or if they can't be drawn.
+    fn render(&self, sender: &ComponentSender<Self>) -> String {
+        let renderers: [&dyn CodeBlockRenderer; 2] = [
+            &Mermaid { dark: self.dark },
+            &Highlighter { dark: self.dark },
+        ];
+        let previous = self.rendered_code.take();
+        let rendered_code = RefCell::new(HashMap::new());
+        let html = markdown_to_html(
+            &self.content,
+            &self.wiki_targets,
+            &self.markdown_options,
+            &|language: &str, code: &str| {
+                let key = (language.to_string(), code.to_string());
+                let html = match previous.get(&key) {
+                    Some(html) => html.clone(),
+                    None if diagram_program(language).is_some() => {
+                        self.draw_diagram(language, code, sender);
+                        return renderers.as_slice().render(language, code);
+                    }
+                    None => renderers.as_slice().render(language, code),
+                };
+                rendered_code.borrow_mut().insert(key, html.clone());
+                html
+            },
+        );
+        self.rendered_code.replace(rendered_code.into_inner());
+
+        html
+    }
+
+    /// Draws a diagram in the background, to be shown once it's done.
+    fn draw_diagram(&self, language: &str, code: &str, sender: &ComponentSender<Self>) {
+        let key = (language.to_string(), code.to_string());
+        if !self.drawing.borrow_mut().insert(key.clone()) {
+            return;
+        }
+
+        let sender = sender.clone();
+        relm4::spawn_local(async move {
+            let (language, code) = key;
+            let html = draw_diagram(&language, &code).await;
+            sender.input(NoteWebViewMsg::DiagramDrawn {
+                language,
+                code,
+                html,
+            });
+        });
+    }
+
+    /// Shows the current content, updating the page in place if possible.
+    fn refresh(&mut self, sender: &ComponentSender<Self>) {
+        if !self.loaded {
+            self.load(sender);
+            return;
+        }
+
+        let html = self.render(sender);
+        // run before the update, which needs them
+        for script in self.missing_scripts(&html) {
+            self.web_view.evaluate_javascript(
+                script,
+                None,
+                None,
+                gio::Cancellable::NONE,
+                |result| {
+                    if let Err(error) = result {
+                        glib::g_warning!(
+                            LOG_DOMAIN,
+                            "could not add script to note view: {}",
+                            error
+                        );
+                    }
+                },
+            );
+        }
+        self.call_script("updateBlocks(html)", "html", &html.to_variant());
+    }
+
+    fn scroll_to_line(&self, line: usize) {
+        self.call_script("scrollToLine(line)", "line", &(line as u32).to_variant());
+    }
+
+    fn call_script(&self, body: &str, name: &str, value: &glib::Variant) {
+        let arguments = glib::VariantDict::new(None);
+        arguments.insert_value(name, value);
+        self.web_view.call_async_javascript_function(
+            body,
+            Some(&arguments.end()),
+            None,
+            None,
+            gio::Cancellable::NONE,
+            |result| {
+                if let Err(error) = result {
+                    glib::g_warning!(LOG_DOMAIN, "could not update note view: {}", error);
+                }
+            },
+        );
+    }
+
+    fn find_highlight(&self) {
+        let find_controller = self.web_view.find_controller().unwrap();
+
+        match &self.highlight {
+            Some(query) => find_controller.search(
+                query,
+                (webkit6::FindOptions::CASE_INSENSITIVE | webkit6::FindOptions::WRAP_AROUND).bits(),
+                u32::MAX,
+            ),
+            None => find_controller.search_finish(),
+        }
+    }
+
+    /// Whether `uri` points to a part of the note shown.
+    fn is_anchor(web_view: &webkit6::WebView, uri: &str) -> bool {
+        let current = web_view.uri().unwrap_or_default();
+
+        uri.split_once('#')
+            .is_some_and(|(document, _)| document == current.split('#').next().unwrap_or_default())
+    }
+
+    /// Tells where to go for a link clicked in the note. Relative links have
+    /// been resolved against the note's location already.
+    fn link_message(uri: &str) -> Option<AppMsg> {
+        let (scheme, rest) = uri.split_once(':')?;
+
+        match scheme {
+            WIKI_LINK_SCHEME => {
+                let target = glib::Uri::unescape_string(rest, None::<&str>)
+                    .map_or(rest.to_string(), |target| target.to_string());
+                Some(AppMsg::OpenWikiLink(target))
+            }
+            RESOURCE_SCHEME | "file" => Some(AppMsg::OpenLocalLink(uri.to_string())),
+            "http" | "https" | "mailto" => Some(AppMsg::ClickedWebLink(uri.to_string())),
+            _ => None,
+        }
+    }
+}
+
+/// Wraps `source` for running in the note view before the page's content.
+fn user_script(source: &str) -> webkit6::UserScript {
+    webkit6::UserScript::new(
+        source,
+        webkit6::UserContentInjectedFrames::TopFrame,
+        webkit6::UserScriptInjectionTime::Start,
+        &[],
+        &[],
+    )
+}
 
 #[relm4::component(pub)]
 impl Component for NoteWebView {
@@ -30,24 +419,34 @@ impl Component for NoteWebView {
             #[local_ref]
             web_view -> webkit6::WebView {
                 set_vexpand: true,
-                #[track(model.changed(NoteWebView::content()))]
-                grab_focus: (),
-                #[track(model.changed(NoteWebView::content()))]
-                load_html[None]: markdown_to_html(model.get_content()).as_str(),
 
-                connect_decide_policy[sender] => move |_, decision, decision_type| {
+                connect_load_changed[sender] => move |_, load_event| {
+                    if load_event == webkit6::LoadEvent::Finished {
+                        sender.input(NoteWebViewMsg::LoadFinished());
+                    }
+                },
+
+                connect_decide_policy[sender] => move |web_view, decision, decision_type| {
                     if decision_type == PolicyDecisionType::NavigationAction {
                         if let Some(nav_decision) = decision.downcast_ref::<NavigationPolicyDecision>(){
                             if let Some(mut nav_action) = nav_decision.navigation_action() {
+                                // loading the note itself
+                                if nav_action.navigation_type() != NavigationType::LinkClicked {
+                                    return false;
+                                }
+
                                 if let Some(request) = nav_action.request() {
                                     let uri = request.uri().expect("uri not to be empty").to_string();
 
-                                    if uri != "about:blank" {
-                                        sender.output_sender().emit(AppMsg::ClickedWebLink(uri));
-
-                                        decision.ignore();
-                                        return true;
+                                    if Self::is_anchor(web_view, &uri) {
+                                        return false;
+                                    }
+                                    if let Some(msg) = Self::link_message(&uri) {
+                                        sender.output_sender().emit(msg);
                                     }
+
+                                    decision.ignore();
+                                    return true;
                                 }
                             }
                         }
@@ -74,21 +473,148 @@ impl Component for NoteWebView {
             h1, h2, h3, h4, h5, h6 {
                 font-family: serif;
             }
+            a.wiki-link.missing {
+                color: #c01c28;
+            }
+            header.frontmatter {
+                margin: 16px 0;
+                padding: 8px 16px;
+                border-radius: 8px;
+                background-color: rgba(0, 0, 0, 0.05);
+                font-size: smaller;
+            }
+            header.frontmatter .title {
+                font-family: serif;
+                font-size: larger;
+                font-weight: bold;
+            }
+            header.frontmatter ul.tags {
+                margin: 4px 0;
+                padding: 0;
+            }
+            header.frontmatter ul.tags li {
+                display: inline-block;
+                margin-right: 6px;
+                padding: 0 6px;
+                border-radius: 4px;
+                background-color: rgba(0, 0, 0, 0.08);
+            }
+            header.frontmatter dl {
+                display: grid;
+                grid-template-columns: max-content auto;
+                gap: 2px 12px;
+                margin: 4px 0;
+            }
+            header.frontmatter dd {
+                margin: 0;
+            }
+            input[type=checkbox][data-offset] {
+                cursor: pointer;
+            }
+            pre {
+                padding: 8px 12px;
+                border-radius: 6px;
+                overflow-x: auto;
+                background-color: rgba(0, 0, 0, 0.05);
+            }
+            div.code-block {
+                position: relative;
+            }
+            pre:has(> .math) {
+                background-color: transparent;
+            }
+            .math.display {
+                text-align: center;
+            }
+            figure.diagram {
+                margin: 16px 0;
+                text-align: center;
+                overflow-x: auto;
+            }
+            figure.diagram .error, .math.error {
+                color: #c01c28;
+            }
+            button.copy-code {
+                position: absolute;
+                top: 4px;
+                right: 4px;
+                opacity: 0;
+            }
+            div.code-block:hover button.copy-code {
+                opacity: 1;
+            }
+            table {
+                border-collapse: collapse;
+            }
+            th, td {
+                padding: 4px 12px;
+                border: 1px solid rgba(0, 0, 0, 0.15);
+            }
+            li:has(> input[type=checkbox]) {
+                list-style: none;
+                margin-left: -1.3em;
+            }
+            section.footnotes {
+                border-top: 1px solid rgba(0, 0, 0, 0.15);
+                font-size: smaller;
+            }
+            .sr-only {
+                position: absolute;
+                width: 1px;
+                height: 1px;
+                overflow: hidden;
+                clip: rect(0, 0, 0, 0);
+            }
             ",
             webkit6::UserContentInjectedFrames::AllFrames,
             webkit6::UserStyleLevel::User,
             &[],
             &[],
         );
-        web_view
-            .user_content_manager()
-            .unwrap()
-            .add_style_sheet(&stylesheet);
+        // scripts are added along with each page, see `load`
+        let user_content_manager = web_view.user_content_manager().unwrap();
+        user_content_manager.add_style_sheet(&stylesheet);
+        user_content_manager.register_script_message_handler(TOGGLE_TASK_HANDLER, None);
+        user_content_manager.register_script_message_handler(COPY_CODE_HANDLER, None);
+        user_content_manager.connect_script_message_received(
+            Some(COPY_CODE_HANDLER),
+            |_, value| {
+                if let Some(display) = gdk::Display::default() {
+                    display.clipboard().set_text(&value.to_str());
+                }
+            },
+        );
+        let output_sender = sender.output_sender().clone();
+        user_content_manager.connect_script_message_received(
+            Some(TOGGLE_TASK_HANDLER),
+            move |_, value| {
+                if value.is_number() && value.to_int32() >= 0 {
+                    output_sender.emit(AppMsg::ToggleTask(value.to_int32() as usize));
+                }
+            },
+        );
 
-        let model = NoteWebView {
+        let style_manager = adw::StyleManager::default();
+        let input_sender = sender.input_sender().clone();
+        style_manager.connect_dark_notify(move |style_manager| {
+            input_sender.emit(NoteWebViewMsg::SetDark(style_manager.is_dark()));
+        });
+
+        let mut model = NoteWebView {
             content,
-            tracker: 0,
+            location: None,
+            wiki_targets: WikiTargets::default(),
+            markdown_options: MarkdownOptions::default(),
+            dark: style_manager.is_dark(),
+            rendered_code: RefCell::default(),
+            drawing: RefCell::default(),
+            page_scripts: Vec::new(),
+            web_view: web_view.clone(),
+            loaded: false,
+            highlight: None,
+            scroll_line: None,
         };
+        model.load(&sender);
 
         let widgets = view_output!();
 
@@ -98,14 +624,75 @@ impl Component for NoteWebView {
     fn update(
         &mut self,
         msg: Self::Input,
-        _sender: ComponentSender<NoteWebView>,
+        sender: ComponentSender<NoteWebView>,
         _root: &Self::Root,
     ) {
-        self.reset();
-
         match msg {
             NoteWebViewMsg::ChangeContent(content) => {
-                self.set_content(content);
+                if content != self.content {
+                    self.content = content;
+                    self.refresh(&sender);
+                }
+            }
+            NoteWebViewMsg::DiagramDrawn {
+                language,
+                code,
+                html,
+            } => {
+                let html =
+                    html.or_else(|| Highlighter { dark: self.dark }.render(&language, &code));
+                let key = (language, code);
+                self.drawing.borrow_mut().remove(&key);
+                self.rendered_code.borrow_mut().insert(key, html);
+                self.refresh(&sender);
+            }
+            NoteWebViewMsg::Focus() => {
+                self.web_view.grab_focus();
+            }
+            NoteWebViewMsg::Highlight(highlight) => {
+                self.highlight = highlight;
+                if !self.web_view.is_loading() {
+                    self.find_highlight();
+                }
+            }
+            NoteWebViewMsg::LoadFinished() => {
+                self.loaded = true;
+                self.find_highlight();
+                if let Some(line) = self.scroll_line.take() {
+                    self.scroll_to_line(line);
+                }
+            }
+            NoteWebViewMsg::ScrollToLine(line) => {
+                if self.loaded {
+                    self.scroll_to_line(line);
+                } else {
+                    self.scroll_line = Some(line);
+                }
+            }
+            NoteWebViewMsg::SetLocation(location) => {
+                if location != self.location {
+                    self.location = location;
+                    self.load(&sender);
+                }
+            }
+            NoteWebViewMsg::SetDark(dark) => {
+                if dark != self.dark {
+                    self.dark = dark;
+                    self.rendered_code.take();
+                    self.refresh(&sender);
+                }
+            }
+            NoteWebViewMsg::SetMarkdownOptions(markdown_options) => {
+                if markdown_options != self.markdown_options {
+                    self.markdown_options = markdown_options;
+                    self.refresh(&sender);
+                }
+            }
+            NoteWebViewMsg::SetWikiTargets(wiki_targets) => {
+                if wiki_targets != self.wiki_targets {
+                    self.wiki_targets = wiki_targets;
+                    self.refresh(&sender);
+                }
             }
         }
     }
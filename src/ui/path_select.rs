@@ -10,6 +10,7 @@ use super::{
 
 pub struct PathSelect {
     path: CollectionPath,
+    items: Vec<Controller<PathSelectItem>>,
 }
 
 #[derive(Debug)]
@@ -40,7 +41,10 @@ impl Component for PathSelect {
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
-        let model = PathSelect { path };
+        let model = PathSelect {
+            path,
+            items: Vec::new(),
+        };
 
         let widgets = view_output!();
 
@@ -61,6 +65,7 @@ impl Component for PathSelect {
                 while let Some(child) = widgets.path_box.last_child() {
                     widgets.path_box.remove(&child);
                 }
+                self.items.clear();
 
                 let mut collections_so_far: Vec<Box<dyn AnyCollection>> = vec![];
                 for (i, collection) in path.iter().enumerate() {
@@ -73,15 +78,25 @@ impl Component for PathSelect {
                     collections_so_far.push(collection.clone());
                     let collection_path = CollectionPath::from(collections_so_far.clone());
 
+                    // the root collection is the notebook itself and can't be renamed
                     let path_select_item = PathSelectItem::builder()
-                        .launch(collection.clone())
+                        .launch((collection.clone(), i > 0))
                         .forward(sender.output_sender(), move |msg| match msg {
                             PathSelectItemOutput::Selected => {
                                 AppMsg::SelectedCollectionPath(collection_path.clone())
                             }
+                            PathSelectItemOutput::Rename => {
+                                AppMsg::StartRenameItem(collection_path.last().clone_box())
+                            }
+                            PathSelectItemOutput::Dropped(item) => AppMsg::MoveItem {
+                                item,
+                                destination: collection_path.last().clone(),
+                            },
                         });
                     widgets.path_box.append(path_select_item.widget());
+                    self.items.push(path_select_item);
                 }
+                self.path = path;
             }
         }
     }
@@ -1,7 +1,14 @@
-use gtk::prelude::ButtonExt;
+use gtk::prelude::*;
+use gtk::{gdk, gio};
+use relm4::actions::{RelmAction, RelmActionGroup};
 use relm4::prelude::*;
 
-use crate::persistence::models::AnyCollection;
+use crate::persistence::models::{AnyCollection, AnyItem};
+
+use super::note_list_item::NoteListItem;
+
+relm4::new_action_group!(PathSelectItemActions, "path-item");
+relm4::new_stateless_action!(RenameAction, PathSelectItemActions, "rename");
 
 #[derive(Debug)]
 pub struct PathSelectItem {
@@ -11,11 +18,14 @@ pub struct PathSelectItem {
 #[derive(Debug)]
 pub enum PathSelectItemOutput {
     Selected,
+    Rename,
+    Dropped(Box<dyn AnyItem>),
 }
 
 #[relm4::component(pub)]
 impl Component for PathSelectItem {
-    type Init = Box<dyn AnyCollection>;
+    /// The collection and whether it may be renamed from the breadcrumbs
+    type Init = (Box<dyn AnyCollection>, bool);
     type Input = ();
     type Output = PathSelectItemOutput;
     type CommandOutput = ();
@@ -32,7 +42,7 @@ impl Component for PathSelectItem {
     }
 
     fn init(
-        collection: Self::Init,
+        (collection, renamable): Self::Init,
         root: Self::Root,
         sender: ComponentSender<Self>,
     ) -> ComponentParts<Self> {
@@ -42,6 +52,44 @@ impl Component for PathSelectItem {
 
         let widgets = view_output!();
 
+        let drop_target = NoteListItem::drop_target();
+        let sender_clone = sender.clone();
+        drop_target.connect_drop(move |_, value, _, _| {
+            match NoteListItem::item_from_value(value) {
+                Some(item) => sender_clone
+                    .output(PathSelectItemOutput::Dropped(item))
+                    .is_ok(),
+                None => false,
+            }
+        });
+        root.add_controller(drop_target);
+
+        if renamable {
+            let mut group = RelmActionGroup::<PathSelectItemActions>::new();
+            let sender_clone = sender.clone();
+            let rename_action: RelmAction<RenameAction> = RelmAction::new_stateless(move |_| {
+                let _ = sender_clone.output(PathSelectItemOutput::Rename);
+            });
+            group.add_action(rename_action);
+            group.register_for_widget(&root);
+
+            let menu = gio::Menu::new();
+            menu.append_item(&RelmAction::<RenameAction>::to_menu_item("Rename…"));
+            let popover = gtk::PopoverMenu::from_model(Some(&menu));
+            popover.set_parent(&root);
+            popover.set_has_arrow(false);
+
+            let gesture = gtk::GestureClick::builder()
+                .button(gdk::BUTTON_SECONDARY)
+                .build();
+            gesture.connect_pressed(move |gesture, _, x, y| {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                popover.popup();
+            });
+            root.add_controller(gesture);
+        }
+
         ComponentParts { model, widgets }
     }
 }
@@ -0,0 +1,128 @@
+use std::cell::Ref;
+
+use crate::persistence::storage::SearchMatch;
+use gtk::prelude::*;
+use gtk::{self, glib, pango};
+use relm4::view;
+
+#[derive(Debug)]
+pub struct SearchResultItem {
+    pub search_match: SearchMatch,
+}
+
+pub struct SearchResultItemWidgets {
+    pub name: gtk::Label,
+    pub snippet: gtk::Label,
+    pub path: gtk::Label,
+}
+
+impl SearchResultItem {
+    pub fn from_search_match(search_match: SearchMatch) -> Self {
+        Self { search_match }
+    }
+
+    pub fn search_match_from_object(obj: &glib::Object) -> Option<SearchMatch> {
+        obj.downcast_ref::<glib::BoxedAnyObject>()
+            .and_then(|boxed| {
+                boxed
+                    .try_borrow::<SearchResultItem>()
+                    .ok()
+                    .map(|search_result_item| search_result_item.search_match.clone())
+            })
+    }
+
+    /// Builds a factory for showing search results in a list view.
+    pub fn factory() -> gtk::SignalListItemFactory {
+        let factory = gtk::SignalListItemFactory::new();
+
+        factory.connect_setup(move |_factory, list_item| {
+            let list_item: &gtk::ListItem = list_item
+                .downcast_ref::<gtk::ListItem>()
+                .expect("Must be a gtk::ListItem");
+
+            let (root, widgets) = SearchResultItem::setup(list_item);
+            unsafe {
+                root.set_data("widgets", widgets);
+            }
+            list_item.set_child(Some(&root));
+        });
+
+        factory.connect_bind(move |_factory, list_item| {
+            let list_item: &gtk::ListItem = list_item
+                .downcast_ref::<gtk::ListItem>()
+                .expect("Must be a gtk::ListItem");
+            let obj = list_item.item().unwrap();
+            let search_result_item: Ref<SearchResultItem> =
+                obj.downcast_ref::<glib::BoxedAnyObject>().unwrap().borrow();
+
+            let mut root = list_item.child().unwrap();
+            let mut widgets: SearchResultItemWidgets =
+                unsafe { root.steal_data("widgets") }.unwrap();
+            search_result_item.bind(&mut root, &mut widgets);
+            unsafe {
+                root.set_data("widgets", widgets);
+            }
+        });
+
+        factory
+    }
+
+    pub fn setup(_list_item: &gtk::ListItem) -> (gtk::Box, SearchResultItemWidgets) {
+        view! {
+            root = gtk::Box {
+                set_orientation: gtk::Orientation::Vertical,
+                set_spacing: 2,
+                set_margin_top: 8,
+                set_margin_bottom: 8,
+                set_focusable: false,
+
+                #[name = "name"]
+                gtk::Label {
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: pango::EllipsizeMode::End,
+                    add_css_class: "heading",
+                },
+                #[name = "snippet"]
+                gtk::Label {
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: pango::EllipsizeMode::End,
+                },
+                #[name = "path"]
+                gtk::Label {
+                    set_halign: gtk::Align::Start,
+                    set_ellipsize: pango::EllipsizeMode::Start,
+                    add_css_class: "caption",
+                    add_css_class: "dim-label",
+                },
+            }
+        }
+
+        let widgets = SearchResultItemWidgets {
+            name,
+            snippet,
+            path,
+        };
+
+        (root, widgets)
+    }
+
+    pub fn bind(&self, root: &mut gtk::Widget, widgets: &mut SearchResultItemWidgets) {
+        let search_match = &self.search_match;
+        let path = search_match
+            .path
+            .iter()
+            .map(|collection| collection.name())
+            .collect::<Vec<String>>()
+            .join(" / ");
+
+        widgets.name.set_text(&search_match.note.name());
+        widgets.snippet.set_text(&search_match.line);
+        widgets.path.set_text(&path);
+
+        root.set_tooltip_text(Some(&format!(
+            "line {}: {}",
+            search_match.line_number + 1,
+            search_match.line
+        )));
+    }
+}
@@ -2,20 +2,38 @@ use std::{cell::Ref, convert::identity};
 
 use crate::{
     icon_names,
-    persistence::models::{AnyItem, CollectionPath},
+    persistence::{
+        models::{AnyItem, CollectionPath, ItemKind},
+        storage::{ItemEvent, SearchMatch},
+    },
     ui::{
         note_list_item::{NoteListItem, NoteListItemWidgets},
         path_select::PathSelectMsg,
+        search_result_item::SearchResultItem,
     },
 };
 use gtk::glib::{self};
 use gtk::{
+    gdk,
     gio::{self},
     prelude::*,
 };
+use relm4::actions::{ActionName, RelmAction, RelmActionGroup};
 use relm4::prelude::*;
 
-use super::{app::AppMsg, path_select::PathSelect};
+use super::{
+    app::{AppMsg, ClipboardContent},
+    path_select::PathSelect,
+};
+
+relm4::new_action_group!(pub SidebarActions, "sidebar");
+relm4::new_stateless_action!(pub RenameItemAction, SidebarActions, "rename-item");
+relm4::new_stateless_action!(pub DeleteItemAction, SidebarActions, "delete-item");
+relm4::new_stateless_action!(pub CutItemAction, SidebarActions, "cut-item");
+relm4::new_stateless_action!(pub CopyItemAction, SidebarActions, "copy-item");
+relm4::new_stateless_action!(pub PasteItemAction, SidebarActions, "paste-item");
+
+const ALL_TAGS: &str = "all tags";
 
 #[derive(Debug, Clone)]
 pub enum SortOrder {
@@ -55,10 +73,163 @@ pub struct Sidebar {
     note_list_model: gtk::SingleSelection,
     note_filter_list_model: gtk::FilterListModel,
     note_sort_list_model: gtk::SortListModel,
+    search_results_store: gio::ListStore,
+    search_results_model: gtk::SingleSelection,
+    /// the query the search results are shown for
+    search_query: Option<String>,
+    /// tags offered in the tag dropdown, after the entry for all notes
+    tags: Vec<String>,
+    tag_list: gtk::StringList,
+    /// the tag whose notes are shown
+    selected_tag: Option<String>,
     path_select: Controller<PathSelect>,
+    rename_item_action: RelmAction<RenameItemAction>,
 }
 
 impl Sidebar {
+    fn selected_item(&self) -> Option<Box<dyn AnyItem>> {
+        self.note_list_model.selected_item().map(|obj| {
+            let note_list_item: Ref<NoteListItem> =
+                obj.downcast_ref::<glib::BoxedAnyObject>().unwrap().borrow();
+            note_list_item.item.clone()
+        })
+    }
+
+    fn position_of(&self, location: &str) -> Option<u32> {
+        (0..self.note_list_store.n_items()).find(|&position| {
+            self.note_list_store
+                .item(position)
+                .and_then(|obj| NoteListItem::item_from_object(&obj))
+                .is_some_and(|item| item.location() == location)
+        })
+    }
+
+    /// Adds `item` to the list, replacing the entry at `location` if there is one.
+    fn put_item(&self, location: &str, item: Box<dyn AnyItem>) {
+        let obj = glib::BoxedAnyObject::new(NoteListItem::from_any_item(item));
+        match self.position_of(location) {
+            Some(position) => self.note_list_store.splice(position, 1, &[obj]),
+            None => self.note_list_store.append(&obj),
+        }
+    }
+
+    fn attach_context_menu(
+        root: &gtk::Box,
+        list_item: &gtk::ListItem,
+        menu: &gio::Menu,
+        sender: AsyncComponentSender<Self>,
+    ) {
+        let popover = gtk::PopoverMenu::from_model(Some(menu));
+        popover.set_parent(root);
+        popover.set_has_arrow(false);
+        popover.set_halign(gtk::Align::Start);
+
+        let gesture = gtk::GestureClick::builder()
+            .button(gdk::BUTTON_SECONDARY)
+            .build();
+        let list_item = list_item.downgrade();
+        gesture.connect_pressed(move |gesture, _, x, y| {
+            if let Some(list_item) = list_item.upgrade() {
+                gesture.set_state(gtk::EventSequenceState::Claimed);
+                sender.input(SidebarMsg::SelectPosition(list_item.position()));
+                popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                popover.popup();
+            }
+        });
+        root.add_controller(gesture);
+    }
+
+    /// Lets rows be dragged onto collection rows to move them there.
+    fn attach_drag_and_drop(
+        root: &gtk::Box,
+        list_item: &gtk::ListItem,
+        sender: AsyncComponentSender<Self>,
+    ) {
+        let drag_source = gtk::DragSource::builder()
+            .actions(gdk::DragAction::MOVE)
+            .build();
+        let list_item_weak = list_item.downgrade();
+        drag_source.connect_prepare(move |_, _, _| {
+            list_item_weak
+                .upgrade()
+                .and_then(|list_item| list_item.item())
+                .map(|obj| gdk::ContentProvider::for_value(&obj.to_value()))
+        });
+        root.add_controller(drag_source);
+
+        let target_collection = {
+            let list_item_weak = list_item.downgrade();
+            move || {
+                list_item_weak
+                    .upgrade()
+                    .and_then(|list_item| list_item.item())
+                    .and_then(|obj| NoteListItem::item_from_object(&obj))
+                    .and_then(|item| item.as_collection())
+            }
+        };
+
+        let drop_target = NoteListItem::drop_target();
+        let target_collection_clone = target_collection.clone();
+        drop_target.connect_motion(move |_, _, _| {
+            if target_collection_clone().is_some() {
+                gdk::DragAction::MOVE
+            } else {
+                gdk::DragAction::empty()
+            }
+        });
+        drop_target.connect_drop(move |_, value, _, _| {
+            let (Some(item), Some(destination)) =
+                (NoteListItem::item_from_value(value), target_collection())
+            else {
+                return false;
+            };
+            if item.location() == destination.location() {
+                return false;
+            }
+
+            let _ = sender.output(AppMsg::MoveItem { item, destination });
+            true
+        });
+        root.add_controller(drop_target);
+    }
+
+    fn hide_search_results(&mut self, widgets: &SidebarWidgets) {
+        self.search_query = None;
+        self.search_results_store.remove_all();
+        widgets.list_stack.set_visible_child_name("items");
+        self.select_tag(widgets, None);
+    }
+
+    /// Selects `tag` in the tag dropdown without filtering by it.
+    fn select_tag(&mut self, widgets: &SidebarWidgets, tag: Option<String>) {
+        let position = tag
+            .as_ref()
+            .and_then(|tag| self.tags.iter().position(|known| known == tag))
+            .map_or(0, |index| index as u32 + 1);
+
+        widgets.tag_dropdown.block_signal(&widgets.tag_handler);
+        widgets.tag_dropdown.set_selected(position);
+        widgets.tag_dropdown.unblock_signal(&widgets.tag_handler);
+        widgets.rename_tag_button.set_sensitive(tag.is_some());
+        self.selected_tag = tag;
+    }
+
+    fn clipboard_shortcuts() -> gtk::ShortcutController {
+        let shortcuts = gtk::ShortcutController::new();
+        for (trigger, action) in [
+            ("<Control>x", CutItemAction::action_name()),
+            ("<Control>c", CopyItemAction::action_name()),
+            ("<Control>v", PasteItemAction::action_name()),
+        ] {
+            shortcuts.add_shortcut(gtk::Shortcut::new(
+                gtk::ShortcutTrigger::parse_string(trigger),
+                Some(gtk::NamedAction::new(&action)),
+            ));
+        }
+
+        shortcuts
+    }
+
     fn build_sorter(&self, sort_order: SortOrder) -> impl IsA<gtk::Sorter> {
         gtk::CustomSorter::new(move |lhs, rhs| {
             let lhs_note_list_item: Ref<NoteListItem> =
@@ -115,7 +286,23 @@ impl Sidebar {
 #[derive(Debug)]
 pub enum SidebarMsg {
     SelectedItem(u32),
+    SelectPosition(u32),
+    RenameSelectedItem(),
+    DeleteSelectedItem(),
+    CutSelectedItem(),
+    CopySelectedItem(),
+    PasteItem(),
     UpdateNoteList(Vec<Box<dyn AnyItem>>),
+    SearchContents(),
+    ShowSearchResults {
+        query: String,
+        results: Vec<SearchMatch>,
+    },
+    SelectedSearchResult(u32),
+    SetTags(Vec<(String, usize)>),
+    SelectedTag(u32),
+    RenameSelectedTag(),
+    ApplyItemEvent(ItemEvent),
     FocusNoteList(),
     FocusSearchEntry(),
     ChangeSearchTerm(String),
@@ -140,6 +327,7 @@ impl AsyncComponent for Sidebar {
             #[name = "search_entry"]
             gtk::Entry {
                 set_placeholder_text: Some("Search (Ctrl-K)"),
+                set_tooltip_text: Some("Type to filter this folder, press Enter to search all notes"),
                 set_hexpand: true,
                 set_icon_from_icon_name: (
                     gtk::EntryIconPosition::Primary,
@@ -154,6 +342,9 @@ impl AsyncComponent for Sidebar {
                     let search_term = entry.buffer().text().to_string();
                     sender.input(Self::Input::ChangedSearchTerm(search_term));
                 } @change_handler,
+                connect_activate[sender] => move |_| {
+                    sender.input(Self::Input::SearchContents());
+                },
                 connect_icon_press[sender] => move |_, icon_position|{
                     if icon_position == gtk::EntryIconPosition::Secondary{
                         sender.input(Self::Input::ChangeSearchTerm(String::from("")));
@@ -161,53 +352,112 @@ impl AsyncComponent for Sidebar {
                 }
             },
 
-            #[name = "sort_dropdown"]
-            gtk::DropDown{
-                set_model: Some(&sort_options),
-                set_expression: Some(&sort_expression),
-
-                connect_selected_notify[sender] => move |dropdown: &gtk::DropDown| {
-                    let obj = dropdown.selected_item().unwrap();
-                    let sort_option: Ref<SortOption> = obj.downcast_ref::<glib::BoxedAnyObject>().unwrap().borrow();
-                    sender
-                        .input_sender()
-                        .emit(SidebarMsg::ChangeSorting(sort_option.order.clone()));
-                }
-            },
-
-            append = model.path_select.widget(),
+            gtk::Box {
+                set_orientation: gtk::Orientation::Horizontal,
+                set_spacing: 4,
+
+                #[name = "sort_dropdown"]
+                gtk::DropDown{
+                    set_hexpand: true,
+                    set_model: Some(&sort_options),
+                    set_expression: Some(&sort_expression),
+
+                    connect_selected_notify[sender] => move |dropdown: &gtk::DropDown| {
+                        let obj = dropdown.selected_item().unwrap();
+                        let sort_option: Ref<SortOption> = obj.downcast_ref::<glib::BoxedAnyObject>().unwrap().borrow();
+                        sender
+                            .input_sender()
+                            .emit(SidebarMsg::ChangeSorting(sort_option.order.clone()));
+                    }
+                },
 
-            gtk::ScrolledWindow {
-                set_vexpand: true,
+                #[name = "tag_dropdown"]
+                gtk::DropDown {
+                    set_hexpand: true,
+                    set_model: Some(&model.tag_list),
+                    set_enable_search: true,
+                    set_tooltip_text: Some("Show notes with this tag in all folders"),
 
-                #[name = "list_view"]
-                gtk::ListView {
-                    set_factory: Some(&factory),
-                    set_model: Some(&model.note_list_model),
-                    set_single_click_activate: true,
-                    set_tab_behavior: gtk::ListTabBehavior::Item,
+                    connect_selected_notify[sender] => move |dropdown: &gtk::DropDown| {
+                        sender.input(SidebarMsg::SelectedTag(dropdown.selected()));
+                    } @tag_handler
+                },
 
-                    connect_activate[sender] => move |_, index| {
-                        sender.input_sender().emit(SidebarMsg::SelectedItem(index));
-                    }
+                #[name = "rename_tag_button"]
+                gtk::Button {
+                    set_icon_name: icon_names::EDIT_REGULAR,
+                    set_tooltip_text: Some("Rename or merge tag…"),
+                    set_sensitive: false,
+                    connect_clicked => SidebarMsg::RenameSelectedTag(),
                 },
+            },
+
+            append = model.path_select.widget(),
+
+            #[name = "list_stack"]
+            gtk::Stack {
+                add_child = &gtk::ScrolledWindow {
+                    set_vexpand: true,
+
+                    #[name = "list_view"]
+                    gtk::ListView {
+                        set_factory: Some(&factory),
+                        set_model: Some(&model.note_list_model),
+                        set_single_click_activate: true,
+                        set_tab_behavior: gtk::ListTabBehavior::Item,
+                        add_controller: Self::clipboard_shortcuts(),
+
+                        connect_activate[sender] => move |_, index| {
+                            sender.input_sender().emit(SidebarMsg::SelectedItem(index));
+                        }
+                    },
+                } -> { set_name: "items" },
+                add_child = &gtk::ScrolledWindow {
+                    set_vexpand: true,
+
+                    gtk::ListView {
+                        set_factory: Some(&SearchResultItem::factory()),
+                        set_model: Some(&model.search_results_model),
+                        set_single_click_activate: true,
+
+                        connect_activate[sender] => move |_, index| {
+                            sender.input_sender().emit(SidebarMsg::SelectedSearchResult(index));
+                        }
+                    },
+                } -> { set_name: "results" },
+                add_child = &gtk::Label {
+                    set_label: "No matching notes",
+                    add_css_class: "dim-label",
+                } -> { set_name: "no-results" },
             }
         }
     }
 
     async fn init(
         collection_path: Self::Init,
-        _root: Self::Root,
+        root: Self::Root,
         sender: AsyncComponentSender<Self>,
     ) -> AsyncComponentParts<Self> {
+        let item_menu = gio::Menu::new();
+        item_menu.append_item(&RelmAction::<RenameItemAction>::to_menu_item("Rename…"));
+        item_menu.append_item(&RelmAction::<DeleteItemAction>::to_menu_item("Delete…"));
+        let clipboard_section = gio::Menu::new();
+        clipboard_section.append_item(&RelmAction::<CutItemAction>::to_menu_item("Cut"));
+        clipboard_section.append_item(&RelmAction::<CopyItemAction>::to_menu_item("Copy"));
+        clipboard_section.append_item(&RelmAction::<PasteItemAction>::to_menu_item("Paste"));
+        item_menu.append_section(None, &clipboard_section);
+
         let factory = gtk::SignalListItemFactory::new();
 
+        let sender_clone = sender.clone();
         factory.connect_setup(move |_factory, list_item| {
             let list_item: &gtk::ListItem = list_item
                 .downcast_ref::<gtk::ListItem>()
                 .expect("Must be a gtk::ListItem");
 
             let (root, widgets) = NoteListItem::setup(list_item);
+            Self::attach_context_menu(&root, list_item, &item_menu, sender_clone.clone());
+            Self::attach_drag_and_drop(&root, list_item, sender_clone.clone());
 
             unsafe {
                 root.set_data("widgets", widgets);
@@ -245,16 +495,69 @@ impl AsyncComponent for Sidebar {
             .model(&note_sort_list_model)
             .build();
 
+        // search results
+        let search_results_store = gio::ListStore::builder()
+            .item_type(glib::BoxedAnyObject::static_type())
+            .build();
+        let search_results_model = gtk::SingleSelection::builder()
+            .model(&search_results_store)
+            .build();
+
         let path_select: Controller<PathSelect> = PathSelect::builder()
             .launch(collection_path.clone())
             .forward(sender.output_sender(), identity);
 
+        // setup actions
+
+        let mut group = RelmActionGroup::<SidebarActions>::new();
+
+        let sender_clone = sender.clone();
+        let rename_item_action: RelmAction<RenameItemAction> =
+            RelmAction::new_stateless(move |_| {
+                sender_clone.input(SidebarMsg::RenameSelectedItem());
+            });
+        group.add_action(rename_item_action.clone());
+
+        let sender_clone = sender.clone();
+        let delete_item_action: RelmAction<DeleteItemAction> =
+            RelmAction::new_stateless(move |_| {
+                sender_clone.input(SidebarMsg::DeleteSelectedItem());
+            });
+        group.add_action(delete_item_action);
+
+        let sender_clone = sender.clone();
+        let cut_item_action: RelmAction<CutItemAction> = RelmAction::new_stateless(move |_| {
+            sender_clone.input(SidebarMsg::CutSelectedItem());
+        });
+        group.add_action(cut_item_action);
+
+        let sender_clone = sender.clone();
+        let copy_item_action: RelmAction<CopyItemAction> = RelmAction::new_stateless(move |_| {
+            sender_clone.input(SidebarMsg::CopySelectedItem());
+        });
+        group.add_action(copy_item_action);
+
+        let sender_clone = sender.clone();
+        let paste_item_action: RelmAction<PasteItemAction> = RelmAction::new_stateless(move |_| {
+            sender_clone.input(SidebarMsg::PasteItem());
+        });
+        group.add_action(paste_item_action);
+
+        group.register_for_widget(&root);
+
         let model = Self {
             note_list_store,
             note_filter_list_model,
             note_sort_list_model,
             note_list_model,
+            search_results_store,
+            search_results_model,
+            search_query: None,
+            tags: Vec::new(),
+            tag_list: gtk::StringList::new(&[ALL_TAGS]),
+            selected_tag: None,
             path_select,
+            rename_item_action,
         };
 
         let mut sort_options = gio::ListStore::new::<glib::BoxedAnyObject>();
@@ -293,12 +596,136 @@ impl AsyncComponent for Sidebar {
                     let _ = sender.output(AppMsg::SelectedItem(item.item.clone()));
                 }
             }
+            SelectPosition(index) => {
+                self.note_list_model.set_selected(index);
+
+                let kind = self.selected_item().map(|item| item.kind());
+                self.rename_item_action.set_enabled(matches!(
+                    kind,
+                    Some(ItemKind::Note) | Some(ItemKind::Collection)
+                ));
+            }
+            RenameSelectedItem() => {
+                if let Some(item) = self.selected_item() {
+                    let _ = sender.output(AppMsg::StartRenameItem(item));
+                }
+            }
+            DeleteSelectedItem() => {
+                if let Some(item) = self.selected_item() {
+                    let _ = sender.output(AppMsg::StartDeleteItem(item));
+                }
+            }
+            CutSelectedItem() => {
+                if let Some(item) = self.selected_item() {
+                    let _ = sender.output(AppMsg::SetClipboard(ClipboardContent::Cut(item)));
+                }
+            }
+            CopySelectedItem() => {
+                if let Some(item) = self.selected_item() {
+                    let _ = sender.output(AppMsg::SetClipboard(ClipboardContent::Copied(item)));
+                }
+            }
+            PasteItem() => {
+                let _ = sender.output(AppMsg::Paste());
+            }
             UpdateNoteList(items) => {
                 self.note_list_store.remove_all();
                 self.note_list_store.extend(items.iter().map(|item| {
                     glib::BoxedAnyObject::new(NoteListItem::from_any_item(item.clone()))
                 }));
             }
+            SearchContents() => {
+                let query = widgets.search_entry.buffer().text().trim().to_string();
+                if !query.is_empty() {
+                    self.select_tag(widgets, None);
+                    let _ = sender.output(AppMsg::Search(query));
+                }
+            }
+            ShowSearchResults { query, results } => {
+                let visible_child_name = if results.is_empty() {
+                    "no-results"
+                } else {
+                    "results"
+                };
+
+                self.search_query = Some(query);
+                self.search_results_store.remove_all();
+                self.search_results_store
+                    .extend(results.into_iter().map(|search_match| {
+                        glib::BoxedAnyObject::new(SearchResultItem::from_search_match(search_match))
+                    }));
+                widgets
+                    .list_stack
+                    .set_visible_child_name(visible_child_name);
+            }
+            SelectedSearchResult(index) => {
+                let search_match = self
+                    .search_results_model
+                    .item(index)
+                    .and_then(|obj| SearchResultItem::search_match_from_object(&obj));
+                if let (Some(search_match), Some(query)) = (search_match, self.search_query.clone())
+                {
+                    let _ = sender.output(AppMsg::OpenSearchResult {
+                        search_match,
+                        query,
+                    });
+                }
+            }
+            SetTags(tags) => {
+                let titles: Vec<String> = tags
+                    .iter()
+                    .map(|(tag, count)| format!("#{} ({})", tag, count))
+                    .collect();
+                let titles: Vec<&str> = titles.iter().map(String::as_str).collect();
+
+                self.tags = tags.into_iter().map(|(tag, _)| tag).collect();
+                widgets.tag_dropdown.block_signal(&widgets.tag_handler);
+                self.tag_list
+                    .splice(1, self.tag_list.n_items() - 1, &titles);
+                widgets.tag_dropdown.unblock_signal(&widgets.tag_handler);
+
+                match self.selected_tag.clone() {
+                    Some(tag) if self.tags.contains(&tag) => self.select_tag(widgets, Some(tag)),
+                    // the tag is gone, e.g. after renaming it
+                    Some(_) => self.hide_search_results(widgets),
+                    None => self.select_tag(widgets, None),
+                }
+            }
+            SelectedTag(position) => {
+                let tag = (position as usize)
+                    .checked_sub(1)
+                    .and_then(|index| self.tags.get(index))
+                    .cloned();
+                match tag {
+                    Some(tag) => {
+                        self.select_tag(widgets, Some(tag.clone()));
+                        let _ = sender.output(AppMsg::ShowTaggedNotes(tag));
+                    }
+                    None => self.hide_search_results(widgets),
+                }
+            }
+            RenameSelectedTag() => {
+                if let Some(tag) = self.selected_tag.clone() {
+                    let _ = sender.output(AppMsg::StartRenameTag(tag));
+                }
+            }
+            ApplyItemEvent(event) => match event {
+                ItemEvent::Created(item) | ItemEvent::Changed(item) => {
+                    self.put_item(&item.location(), item);
+                }
+                ItemEvent::Deleted { location } => {
+                    if let Some(position) = self.position_of(&location) {
+                        self.note_list_store.remove(position);
+                    }
+                }
+                ItemEvent::Moved { location, item } => {
+                    if let Some(position) = self.position_of(&item.location()) {
+                        // moved over an existing item, e.g. when saving via a temporary file
+                        self.note_list_store.remove(position);
+                    }
+                    self.put_item(&location, item);
+                }
+            },
             FocusNoteList() => {
                 widgets.list_view.grab_focus();
             }
@@ -306,6 +733,7 @@ impl AsyncComponent for Sidebar {
                 widgets.search_entry.grab_focus();
             }
             ChangeSearchTerm(search_term) => {
+                self.hide_search_results(widgets);
                 widgets.search_entry.block_signal(&widgets.change_handler);
                 widgets.search_entry.buffer().set_text(search_term.clone());
                 widgets.search_entry.unblock_signal(&widgets.change_handler);
@@ -313,6 +741,7 @@ impl AsyncComponent for Sidebar {
                 self.note_filter_list_model.set_filter(Some(&filter));
             }
             ChangedSearchTerm(search_term) => {
+                self.hide_search_results(widgets);
                 let filter = self.build_filter(search_term);
                 self.note_filter_list_model.set_filter(Some(&filter));
             }
@@ -321,6 +750,7 @@ impl AsyncComponent for Sidebar {
                 self.note_sort_list_model.set_sorter(Some(&sorter));
             }
             SetCollectionPath(collection_path) => {
+                self.hide_search_results(widgets);
                 self.path_select
                     .emit(PathSelectMsg::SetCollectionPath(collection_path));
             }
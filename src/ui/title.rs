@@ -11,16 +11,25 @@ pub enum TitleMode {
     EditTitle,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SaveState {
+    Saved,
+    Unsaved,
+    Saving,
+}
+
 #[derive(Debug)]
 pub enum TitleMsg {
     SetCurrentNote(Option<Box<dyn AnyNote>>),
     SetMode(TitleMode),
+    SetSaveState(SaveState),
     RenameNote(String),
 }
 
 pub struct Title {
     current_note: Option<Box<dyn AnyNote>>,
     mode: TitleMode,
+    save_state: SaveState,
 }
 
 #[relm4::component(pub)]
@@ -43,6 +52,13 @@ impl Component for Title {
                         "".to_string()
                     }).as_str(),
                 #[watch]
+                set_subtitle: match (&model.current_note, model.save_state) {
+                    (None, _) => "",
+                    (Some(_), SaveState::Saved) => "Saved",
+                    (Some(_), SaveState::Unsaved) => "Unsaved changes",
+                    (Some(_), SaveState::Saving) => "Saving…",
+                },
+                #[watch]
                 set_visible: model.mode == TitleMode::Normal,
             },
             gtk::Entry {
@@ -81,6 +97,7 @@ impl Component for Title {
         let model = Self {
             current_note: None,
             mode: TitleMode::Normal,
+            save_state: SaveState::Saved,
         };
 
         let widgets = view_output!();
@@ -99,6 +116,9 @@ impl Component for Title {
             TitleMsg::SetCurrentNote(note) => {
                 self.current_note = note;
             }
+            TitleMsg::SetSaveState(save_state) => {
+                self.save_state = save_state;
+            }
             TitleMsg::SetMode(mode) => {
                 self.mode = mode;
             }
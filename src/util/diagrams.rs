@@ -0,0 +1,147 @@
+use std::{ffi::OsStr, time::Duration};
+
+use gtk::{gio, glib};
+
+use super::markdown::{CodeBlockRenderer, escape_html};
+use crate::LOG_DOMAIN;
+
+/// Longest a program may take to draw a diagram, before it's stopped and the
+/// code is shown instead.
+const DRAW_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mermaid, drawing diagrams in the note view without needing the network.
+pub const MERMAID_SCRIPT: &str = include_str!("../../resources/mermaid/mermaid.min.js");
+
+/// The command drawing code blocks in `language` as SVG, e.g. `dot` for
+/// ```` ```dot ```` and ```` ```graphviz ```` blocks.
+pub fn diagram_program(language: &str) -> Option<&'static [&'static str]> {
+    match language {
+        "dot" | "graphviz" => Some(&["dot", "-Tsvg"]),
+        "plantuml" | "puml" => Some(&["plantuml", "-tsvg", "-pipe"]),
+        _ => None,
+    }
+}
+
+/// Leaves ```` ```mermaid ```` blocks to the note view, which draws them with
+/// the bundled Mermaid script.
+pub struct Mermaid {
+    pub dark: bool,
+}
+
+impl CodeBlockRenderer for Mermaid {
+    fn render(&self, language: &str, code: &str) -> Option<String> {
+        if language != "mermaid" {
+            return None;
+        }
+
+        Some(format!(
+            "<figure class=\"diagram mermaid\" data-dark=\"{}\">{}</figure>",
+            self.dark,
+            escape_html(code)
+        ))
+    }
+}
+
+/// Draws a code block with the program for its language, without blocking
+/// the main loop. Returns `None` if the program isn't installed, fails or
+/// takes too long, so that the code is shown instead.
+pub async fn draw_diagram(language: &str, code: &str) -> Option<String> {
+    let command = diagram_program(language)?;
+    let program = command[0];
+    glib::find_program_in_path(program)?;
+
+    let argv: Vec<&OsStr> = command.iter().map(OsStr::new).collect();
+    let process = gio::Subprocess::newv(
+        &argv,
+        gio::SubprocessFlags::STDIN_PIPE
+            | gio::SubprocessFlags::STDOUT_PIPE
+            | gio::SubprocessFlags::STDERR_PIPE,
+    );
+    let process = match process {
+        Ok(process) => process,
+        Err(error) => {
+            glib::g_warning!(LOG_DOMAIN, "could not run {}: {}", program, error);
+            return None;
+        }
+    };
+
+    let input = glib::Bytes::from(code.as_bytes());
+    let output =
+        glib::future_with_timeout(DRAW_TIMEOUT, process.communicate_future(Some(&input))).await;
+    let (stdout, stderr) = match output {
+        Ok(Ok(output)) => output,
+        Ok(Err(error)) => {
+            glib::g_warning!(LOG_DOMAIN, "could not run {}: {}", program, error);
+            return None;
+        }
+        Err(_) => {
+            process.force_exit();
+            glib::g_warning!(
+                LOG_DOMAIN,
+                "{} took longer than {} seconds",
+                program,
+                DRAW_TIMEOUT.as_secs()
+            );
+            return None;
+        }
+    };
+
+    if !process.is_successful() {
+        let stderr = stderr.unwrap_or_else(|| glib::Bytes::from_static(b""));
+        glib::g_warning!(
+            LOG_DOMAIN,
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&stderr).trim()
+        );
+        return None;
+    }
+
+    let svg = inline_svg(&String::from_utf8_lossy(&stdout?))?;
+    Some(format!("<figure class=\"diagram\">{}</figure>", svg))
+}
+
+/// Turns an SVG document into an element that can be put into HTML, dropping
+/// the XML declaration, doctype and comments.
+fn inline_svg(document: &str) -> Option<String> {
+    let mut rest = &document[document.find("<svg")?..];
+    let mut svg = String::with_capacity(rest.len());
+    while let Some(start) = rest.find("<!--") {
+        svg.push_str(&rest[..start]);
+        rest = rest[start..]
+            .find("-->")
+            .map_or("", |end| &rest[start + end + 3..]);
+    }
+    svg.push_str(rest);
+
+    Some(svg.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inline_svg() {
+        let document = "<?xml version=\"1.0\"?>\n\
+                        <!DOCTYPE svg PUBLIC \"-//W3C//DTD SVG 1.1//EN\">\n\
+                        <!-- Generated by graphviz -->\n\
+                        <svg width=\"62pt\">\n<!-- a&#45;&gt;b -->\n<g id=\"edge1\"></g>\n</svg>\n";
+
+        assert_eq!(
+            inline_svg(document).as_deref(),
+            Some("<svg width=\"62pt\">\n\n<g id=\"edge1\"></g>\n</svg>")
+        );
+        assert_eq!(inline_svg("not a drawing"), None);
+    }
+
+    #[test]
+    fn test_other_languages_are_left_alone() {
+        assert_eq!(diagram_program("rust"), None);
+        assert_eq!(
+            diagram_program("puml"),
+            Some(&["plantuml", "-tsvg", "-pipe"][..])
+        );
+        assert_eq!(Mermaid { dark: false }.render("dot", "digraph {}"), None);
+    }
+}
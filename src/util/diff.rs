@@ -0,0 +1,171 @@
+use std::iter::once;
+
+const MARKER_MINE: &str = "<<<<<<< mine\n";
+const MARKER_SEPARATOR: &str = "=======\n";
+const MARKER_THEIRS: &str = ">>>>>>> theirs\n";
+
+/// Merges two versions of a text line by line. Lines both versions agree on
+/// are kept, everything else ends up between git-style conflict markers.
+pub fn merge_with_conflict_markers(mine: &str, theirs: &str) -> String {
+    let mine: Vec<&str> = mine.split_inclusive('\n').collect();
+    let theirs: Vec<&str> = theirs.split_inclusive('\n').collect();
+
+    let mut merged = String::new();
+    let (mut i, mut j) = (0, 0);
+    for (k, l) in common_lines(&mine, &theirs)
+        .into_iter()
+        .chain(once((mine.len(), theirs.len())))
+    {
+        push_conflict(&mut merged, &mine[i..k], &theirs[j..l]);
+        if let Some(line) = mine.get(k) {
+            merged.push_str(line);
+        }
+        (i, j) = (k + 1, l + 1);
+    }
+
+    merged
+}
+
+/// Indices of the lines `a` and `b` have in common, using the longest
+/// common subsequence. Found with Hirschberg's algorithm, which needs memory
+/// linear in the length of the texts.
+fn common_lines(a: &[&str], b: &[&str]) -> Vec<(usize, usize)> {
+    let mut common = Vec::new();
+    push_common_lines(a, b, (0, 0), &mut common);
+
+    common
+}
+
+fn push_common_lines(
+    a: &[&str],
+    b: &[&str],
+    offset: (usize, usize),
+    common: &mut Vec<(usize, usize)>,
+) {
+    // lines both start or end with are common anyway, and usually most of them
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    common.extend((0..prefix).map(|k| (offset.0 + k, offset.1 + k)));
+
+    let (a_rest, b_rest) = (&a[prefix..a.len() - suffix], &b[prefix..b.len() - suffix]);
+    let rest_offset = (offset.0 + prefix, offset.1 + prefix);
+    if a_rest.len() == 1 {
+        if let Some(j) = b_rest.iter().position(|line| *line == a_rest[0]) {
+            common.push((rest_offset.0, rest_offset.1 + j));
+        }
+    } else if !a_rest.is_empty() && !b_rest.is_empty() {
+        // split `b` where the common lines of the halves of `a` add up to the most
+        let middle = a_rest.len() / 2;
+        let forward = lcs_lengths(a_rest[..middle].iter(), b_rest.iter());
+        let backward = lcs_lengths(a_rest[middle..].iter().rev(), b_rest.iter().rev());
+        let split = (0..=b_rest.len())
+            .max_by_key(|j| (forward[*j] + backward[b_rest.len() - j], usize::MAX - j))
+            .unwrap_or_default();
+
+        push_common_lines(&a_rest[..middle], &b_rest[..split], rest_offset, common);
+        push_common_lines(
+            &a_rest[middle..],
+            &b_rest[split..],
+            (rest_offset.0 + middle, rest_offset.1 + split),
+            common,
+        );
+    }
+
+    let suffix_start = (offset.0 + a.len() - suffix, offset.1 + b.len() - suffix);
+    common.extend((0..suffix).map(|k| (suffix_start.0 + k, suffix_start.1 + k)));
+}
+
+/// Lengths of the longest common subsequence of all of `a` and each start of
+/// `b`, keeping only one row of the table at a time.
+fn lcs_lengths<'a>(
+    a: impl Iterator<Item = &'a &'a str>,
+    b: impl Iterator<Item = &'a &'a str> + Clone,
+) -> Vec<usize> {
+    let mut previous = vec![0; b.clone().count() + 1];
+    let mut current = previous.clone();
+    for x in a {
+        for (j, y) in b.clone().enumerate() {
+            current[j + 1] = if x == y {
+                previous[j] + 1
+            } else {
+                previous[j + 1].max(current[j])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous
+}
+
+fn push_conflict(merged: &mut String, mine: &[&str], theirs: &[&str]) {
+    if mine.is_empty() && theirs.is_empty() {
+        return;
+    }
+
+    merged.push_str(MARKER_MINE);
+    push_lines(merged, mine);
+    merged.push_str(MARKER_SEPARATOR);
+    push_lines(merged, theirs);
+    merged.push_str(MARKER_THEIRS);
+}
+
+fn push_lines(merged: &mut String, lines: &[&str]) {
+    for line in lines {
+        merged.push_str(line);
+        if !line.ends_with('\n') {
+            merged.push('\n');
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_identical() {
+        let text = "# Title\n\nsome text\n";
+
+        assert_eq!(merge_with_conflict_markers(text, text), text);
+    }
+
+    #[test]
+    fn test_merge_changed_line() {
+        let mine = "# Title\nmine\nend\n";
+        let theirs = "# Title\ntheirs\nend\n";
+
+        assert_eq!(
+            merge_with_conflict_markers(mine, theirs),
+            "# Title\n<<<<<<< mine\nmine\n=======\ntheirs\n>>>>>>> theirs\nend\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_inserted_lines() {
+        let mine = "a\nb\nc\nb\nd\n";
+        let theirs = "a\nx\nb\nc\nd\ny\n";
+
+        assert_eq!(
+            merge_with_conflict_markers(mine, theirs),
+            "a\n<<<<<<< mine\n=======\nx\n>>>>>>> theirs\nb\nc\n\
+             <<<<<<< mine\nb\n=======\n>>>>>>> theirs\nd\n\
+             <<<<<<< mine\n=======\ny\n>>>>>>> theirs\n"
+        );
+    }
+
+    #[test]
+    fn test_merge_appended_line_without_newline() {
+        let mine = "one\ntwo";
+        let theirs = "one\n";
+
+        assert_eq!(
+            merge_with_conflict_markers(mine, theirs),
+            "one\n<<<<<<< mine\ntwo\n=======\n>>>>>>> theirs\n"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+
+use gtk::{pango, prelude::*};
+use sourceview5::prelude::*;
+
+use super::markdown::{CodeBlockRenderer, escape_html};
+
+/// Style schemes code is highlighted with, following the app's theme.
+const LIGHT_SCHEME: &str = "Adwaita";
+const DARK_SCHEME: &str = "Adwaita-dark";
+
+/// Highlights code blocks in languages GtkSourceView knows, for a light or
+/// dark theme.
+pub struct Highlighter {
+    pub dark: bool,
+}
+
+impl CodeBlockRenderer for Highlighter {
+    fn render(&self, language: &str, code: &str) -> Option<String> {
+        highlight_code(code, language, self.dark)
+    }
+}
+
+/// Highlights the code of a fenced code block as a `<pre>` element, using
+/// GtkSourceView's language definitions. `language` is the first word of the
+/// block's info string, e.g. `rust`, `sh` or `sql`. Returns `None` for
+/// languages that aren't known.
+pub fn highlight_code(code: &str, language: &str, dark: bool) -> Option<String> {
+    let buffer = sourceview5::Buffer::with_language(&find_language(language)?);
+    let scheme = sourceview5::StyleSchemeManager::default().scheme(if dark {
+        DARK_SCHEME
+    } else {
+        LIGHT_SCHEME
+    });
+    buffer.set_style_scheme(scheme.as_ref());
+    buffer.set_highlight_syntax(true);
+    buffer.set_text(code);
+
+    let (start, end) = buffer.bounds();
+    buffer.ensure_highlight(&start, &end);
+
+    let mut html = String::with_capacity(code.len() * 2);
+    let mut iter = start;
+    while iter != end {
+        let mut next = iter;
+        next.forward_to_tag_toggle(None::<&gtk::TextTag>);
+        let text = escape_html(&buffer.text(&iter, &next, true));
+        let style = tag_style(&iter.tags());
+        if style.is_empty() {
+            html.push_str(&text);
+        } else {
+            html.push_str(&format!("<span style=\"{}\">{}</span>", style, text));
+        }
+        iter = next;
+    }
+
+    let block_style = scheme
+        .and_then(|scheme| scheme.style("text"))
+        .map(|text| {
+            let mut properties = Vec::new();
+            if let Some(background) = text.background().filter(|_| text.is_background_set()) {
+                properties.push(format!("background-color: {}", background));
+            }
+            if let Some(foreground) = text.foreground().filter(|_| text.is_foreground_set()) {
+                properties.push(format!("color: {}", foreground));
+            }
+            properties.join("; ")
+        })
+        .unwrap_or_default();
+
+    Some(format!(
+        "<pre class=\"highlighted\" style=\"{}\"><code class=\"language-{}\">{}</code></pre>",
+        escape_html(&block_style),
+        escape_html(language),
+        html
+    ))
+}
+
+/// Looks up a language by its id, e.g. `rust`, by a file extension, e.g.
+/// `rs`, or by a common name GtkSourceView doesn't use.
+fn find_language(name: &str) -> Option<sourceview5::Language> {
+    let manager = sourceview5::LanguageManager::default();
+    let name = name.to_lowercase();
+    let id = match name.as_str() {
+        "shell" | "bash" | "zsh" | "console" => "sh",
+        "py" => "python3",
+        "yml" => "yaml",
+        "golang" => "go",
+        "c++" => "cpp",
+        name => name,
+    };
+
+    manager
+        .language(id)
+        .or_else(|| manager.guess_language(Some(PathBuf::from(format!("code.{}", id))), None))
+}
+
+/// Turns the highlighting tags applied to some text into inline CSS. Tags come
+/// in order of priority, later ones win.
+fn tag_style(tags: &[gtk::TextTag]) -> String {
+    let mut properties: Vec<(&str, String)> = Vec::new();
+    let mut set = |name: &'static str, value: String| {
+        properties.retain(|(existing, _)| *existing != name);
+        properties.push((name, value));
+    };
+
+    for tag in tags {
+        if let Some(color) = tag.foreground_rgba().filter(|_| tag.is_foreground_set()) {
+            set("color", color.to_str().to_string());
+        }
+        if let Some(color) = tag.background_rgba().filter(|_| tag.is_background_set()) {
+            set("background-color", color.to_str().to_string());
+        }
+        if tag.is_weight_set() {
+            set("font-weight", tag.weight().to_string());
+        }
+        if tag.is_style_set() {
+            let style = match tag.style() {
+                pango::Style::Italic | pango::Style::Oblique => "italic",
+                _ => "normal",
+            };
+            set("font-style", style.to_string());
+        }
+        if tag.is_underline_set() && tag.underline() != pango::Underline::None {
+            set("text-decoration", "underline".to_string());
+        }
+        if tag.is_strikethrough_set() && tag.is_strikethrough() {
+            set("text-decoration", "line-through".to_string());
+        }
+    }
+
+    properties
+        .iter()
+        .map(|(name, value)| format!("{}: {}", name, value))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
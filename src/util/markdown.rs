@@ -1,5 +1,633 @@
-use markdown::to_html;
+use markdown::{Options, mdast::Node, to_html, to_html_with_options, to_mdast};
 
-pub fn markdown_to_html(text: &str) -> String {
-    to_html(text)
+use crate::persistence::frontmatter::{Frontmatter, Metadata};
+
+use super::wiki::{self, WikiTargets};
+
+/// URI scheme of the links rendered for `[[wiki links]]`.
+pub const WIKI_LINK_SCHEME: &str = "wiki";
+
+const VOID_ELEMENTS: &[&str] = &["br", "hr", "img", "input"];
+
+/// Markdown extensions the notes of a notebook are rendered with. MDX is
+/// never enabled, since notes aren't JavaScript.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MarkdownOptions {
+    /// GitHub-flavored tables, task lists, footnotes, strikethrough and
+    /// autolinks
+    pub gfm: bool,
+    /// frontmatter shown as a card above the note, rather than as text
+    pub frontmatter: bool,
+    /// `$$inline$$` and `$$` display math, typeset by the note view. Single
+    /// dollars are left alone, so that prices like $5 and $10 stay text.
+    pub math: bool,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            gfm: true,
+            frontmatter: true,
+            math: true,
+        }
+    }
+}
+
+impl MarkdownOptions {
+    fn to_options(self) -> Options {
+        let mut options = if self.gfm {
+            Options::gfm()
+        } else {
+            Options::default()
+        };
+        // frontmatter is split off before rendering, see `markdown_to_html`
+        options.parse.constructs.frontmatter = false;
+        options.parse.constructs.math_flow = self.math;
+        options.parse.constructs.math_text = self.math;
+        options.parse.math_text_single_dollar = false;
+
+        options
+    }
+}
+
+/// Renders the code blocks of some languages, e.g. as highlighted code or as
+/// diagrams. Results that are `<pre>` elements get a button for copying the
+/// code.
+pub trait CodeBlockRenderer {
+    /// Returns the HTML shown for a code block in `language`, the first word
+    /// of its info string, or `None` to leave the block as it is.
+    fn render(&self, language: &str, code: &str) -> Option<String>;
+}
+
+impl<F: Fn(&str, &str) -> Option<String>> CodeBlockRenderer for F {
+    fn render(&self, language: &str, code: &str) -> Option<String> {
+        self(language, code)
+    }
+}
+
+impl CodeBlockRenderer for [&dyn CodeBlockRenderer] {
+    /// Uses the first renderer that handles the block.
+    fn render(&self, language: &str, code: &str) -> Option<String> {
+        self.iter()
+            .find_map(|renderer| renderer.render(language, code))
+    }
+}
+
+/// Renders a note, marking each top-level block with the line it starts on,
+/// counting from zero, in a `data-line` attribute. Task list checkboxes are
+/// enabled and carry the offset of their mark in `text` in a `data-offset`
+/// attribute, see [`toggle_task`]. Code blocks are rendered by
+/// `code_renderer`.
+pub fn markdown_to_html(
+    text: &str,
+    wiki_targets: &WikiTargets,
+    markdown_options: &MarkdownOptions,
+    code_renderer: &dyn CodeBlockRenderer,
+) -> String {
+    let frontmatter = Frontmatter::parse(text).filter(|_| markdown_options.frontmatter);
+    let (card, body) = match frontmatter {
+        Some(frontmatter) => (metadata_card(&frontmatter.metadata), frontmatter.body(text)),
+        None => (String::new(), text),
+    };
+    let body_offset = text.len() - body.len();
+    let first_line = text[..body_offset].matches('\n').count();
+    let options = markdown_options.to_options();
+    // only MDX can fail to parse
+    let rendered = to_html_with_options(body, &options).unwrap_or_else(|_| to_html(body));
+    let rendered = render_code_blocks(&rendered, code_renderer);
+    let root = to_mdast(body, &options.parse).ok();
+    let html = format!(
+        "{}{}",
+        card,
+        mark_task_items(
+            &mark_block_lines(&rendered, &block_lines(root.as_ref(), first_line)),
+            &task_offsets(root.as_ref(), body, body_offset),
+        )
+    );
+
+    render_wiki_links(&html, wiki_targets)
+}
+
+/// Returns the lines the top-level blocks rendered for `body` start on.
+fn block_lines(root: Option<&Node>, first_line: usize) -> Vec<usize> {
+    let Some(root) = root else {
+        return Vec::new();
+    };
+
+    root.children()
+        .into_iter()
+        .flatten()
+        // definitions aren't rendered where they are, and raw HTML is escaped
+        // into plain text
+        .filter(|node| {
+            !matches!(
+                node,
+                Node::Definition(_) | Node::FootnoteDefinition(_) | Node::Html(_)
+            )
+        })
+        .filter_map(|node| node.position())
+        .map(|position| first_line + position.start.line - 1)
+        .collect()
+}
+
+/// Returns the offsets of the marks of all task list items in `body`, in the
+/// order they are rendered.
+fn task_offsets(root: Option<&Node>, body: &str, body_offset: usize) -> Vec<usize> {
+    fn collect(node: &Node, body: &str, offsets: &mut Vec<usize>) {
+        if let Node::ListItem(item) = node {
+            let mark = item
+                .checked
+                .and(item.position.as_ref())
+                .and_then(|position| {
+                    body[position.start.offset..]
+                        .find('[')
+                        .map(|index| position.start.offset + index + 1)
+                });
+            offsets.extend(mark);
+        }
+        // footnotes are rendered at the end, their tasks are left alone
+        if matches!(node, Node::FootnoteDefinition(_)) {
+            return;
+        }
+        for child in node.children().into_iter().flatten() {
+            collect(child, body, offsets);
+        }
+    }
+
+    let mut offsets = Vec::new();
+    if let Some(root) = root {
+        collect(root, body, &mut offsets);
+    }
+
+    offsets
+        .into_iter()
+        .map(|offset| body_offset + offset)
+        .collect()
+}
+
+/// Enables the task list checkboxes of `html`, in order, that there are
+/// offsets for.
+fn mark_task_items(html: &str, offsets: &[usize]) -> String {
+    const CHECKBOX: &str = "<input type=\"checkbox\" disabled=\"\"";
+
+    let mut marked = String::with_capacity(html.len());
+    let mut offsets = offsets.iter();
+    let mut rest = html;
+    while let Some(start) = rest.find(CHECKBOX) {
+        marked.push_str(&rest[..start]);
+        match offsets.next() {
+            Some(offset) => marked.push_str(&format!(
+                "<input type=\"checkbox\" data-offset=\"{}\"",
+                offset
+            )),
+            None => marked.push_str(CHECKBOX),
+        }
+        rest = &rest[start + CHECKBOX.len()..];
+    }
+    marked.push_str(rest);
+
+    marked
+}
+
+/// Ticks or unticks the task list item whose mark is at `offset` in `text`.
+/// Returns `None` if there is no such mark there, e.g. because the text
+/// changed since rendering.
+pub fn toggle_task(text: &str, offset: usize) -> Option<String> {
+    let before = text.get(..offset)?;
+    let after = text.get(offset + 1..)?;
+    if !before.ends_with('[') || !after.starts_with(']') {
+        return None;
+    }
+
+    let mark = match text.get(offset..offset + 1)? {
+        " " => "x",
+        "x" | "X" => " ",
+        _ => return None,
+    };
+
+    Some(format!("{}{}{}", before, mark, after))
+}
+
+/// Renders the code blocks of `html` and adds a copy button to those shown as
+/// code. Display math is left alone.
+fn render_code_blocks(html: &str, code_renderer: &dyn CodeBlockRenderer) -> String {
+    const CODE_START: &str = "<pre><code";
+    const CODE_END: &str = "</code></pre>";
+
+    let mut rendered = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(start) = rest.find(CODE_START) {
+        let Some(length) = rest[start..].find(CODE_END) else {
+            break;
+        };
+        let end = start + length + CODE_END.len();
+        let block = &rest[start..end];
+        rendered.push_str(&rest[..start]);
+        rest = &rest[end..];
+
+        let tag_end = block[CODE_START.len()..]
+            .find('>')
+            .map_or(block.len(), |index| CODE_START.len() + index + 1);
+        let class = block[..tag_end]
+            .split_once("class=\"")
+            .and_then(|(_, class)| class.split_once('"'))
+            .map_or("", |(class, _)| class);
+        if class.split(' ').any(|class| class == "math-display") {
+            rendered.push_str(block);
+            continue;
+        }
+
+        let language = class
+            .split(' ')
+            .find_map(|class| class.strip_prefix("language-"));
+        let code = unescape_html(&block[tag_end..block.len() - CODE_END.len()]);
+        match language.and_then(|language| code_renderer.render(language, &code)) {
+            Some(html) if !html.starts_with("<pre") => rendered.push_str(&html),
+            html => rendered.push_str(&format!(
+                "<div class=\"code-block\"><button class=\"copy-code\" type=\"button\">Copy</button>{}</div>",
+                html.as_deref().unwrap_or(block)
+            )),
+        }
+    }
+    rendered.push_str(rest);
+
+    rendered
+}
+
+/// Adds `data-line` attributes to the top-level elements of `html`.
+fn mark_block_lines(html: &str, lines: &[usize]) -> String {
+    let mut marked = String::with_capacity(html.len());
+    let mut lines = lines.iter();
+    let mut depth = 0usize;
+    let mut rest = html;
+
+    while let Some(start) = rest.find('<') {
+        let end = rest[start..]
+            .find('>')
+            .map_or(rest.len(), |index| start + index + 1);
+        let tag = &rest[start..end];
+        marked.push_str(&rest[..start]);
+        rest = &rest[end..];
+
+        if tag.starts_with("</") {
+            depth = depth.saturating_sub(1);
+            marked.push_str(tag);
+            continue;
+        }
+
+        let name_end = tag[1..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .map_or(tag.len(), |index| index + 1);
+        let line = if depth == 0 { lines.next() } else { None };
+        match line {
+            Some(line) => marked.push_str(&format!(
+                "{} data-line=\"{}\"{}",
+                &tag[..name_end],
+                line,
+                &tag[name_end..]
+            )),
+            None => marked.push_str(tag),
+        }
+        if !tag.ends_with("/>") && !VOID_ELEMENTS.contains(&&tag[1..name_end]) {
+            depth += 1;
+        }
+    }
+    marked.push_str(rest);
+
+    marked
+}
+
+/// Turns `[[wiki links]]` left as text by the markdown renderer into links,
+/// marking those to missing notes. Code is left alone.
+fn render_wiki_links(html: &str, wiki_targets: &WikiTargets) -> String {
+    let mut rendered = String::with_capacity(html.len());
+    let mut code_depth = 0;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        if rest.starts_with('<') {
+            let tag_end = rest.find('>').map_or(rest.len(), |index| index + 1);
+            let tag = &rest[..tag_end];
+            if tag.starts_with("<code") || tag.starts_with("<pre") {
+                code_depth += 1;
+            } else if tag.starts_with("</code") || tag.starts_with("</pre") {
+                code_depth -= 1;
+            }
+            rendered.push_str(tag);
+            rest = &rest[tag_end..];
+            continue;
+        }
+
+        let text_end = rest.find('<').unwrap_or(rest.len());
+        let mut text = &rest[..text_end];
+        rest = &rest[text_end..];
+        if code_depth > 0 {
+            rendered.push_str(text);
+            continue;
+        }
+
+        while let Some(start) = text.find("[[") {
+            let Some(length) = text[start + 2..].find("]]") else {
+                break;
+            };
+            let (target, label) = wiki::parse_link(&text[start + 2..start + 2 + length]);
+            let target = unescape_html(target);
+            let class = if wiki_targets.contains(&target) {
+                "wiki-link"
+            } else {
+                "wiki-link missing"
+            };
+
+            rendered.push_str(&text[..start]);
+            rendered.push_str(&format!(
+                "<a href=\"{}:{}\" class=\"{}\">{}</a>",
+                WIKI_LINK_SCHEME,
+                percent_encode(&target),
+                class,
+                label
+            ));
+            text = &text[start + 2 + length + 2..];
+        }
+        rendered.push_str(text);
+    }
+
+    rendered
+}
+
+/// Renders the frontmatter of a note as a card shown above its content.
+fn metadata_card(metadata: &Metadata) -> String {
+    if metadata.is_empty() {
+        return String::new();
+    }
+
+    let mut html = String::from("<header class=\"frontmatter\">");
+    if let Some(title) = &metadata.title {
+        html.push_str(&format!(
+            "<div class=\"title\">{}</div>",
+            escape_html(title)
+        ));
+    }
+    if !metadata.tags.is_empty() {
+        html.push_str("<ul class=\"tags\">");
+        for tag in &metadata.tags {
+            html.push_str(&format!("<li>#{}</li>", escape_html(tag)));
+        }
+        html.push_str("</ul>");
+    }
+
+    let fields = [
+        ("aliases", Some(metadata.aliases.join(", "))),
+        ("created", metadata.created.clone()),
+    ]
+    .into_iter()
+    .filter_map(|(key, value)| {
+        value
+            .filter(|value| !value.is_empty())
+            .map(|value| (key.to_string(), value))
+    })
+    .chain(
+        metadata
+            .custom
+            .iter()
+            .map(|(key, value)| (key.clone(), value.to_text())),
+    )
+    .collect::<Vec<_>>();
+
+    if !fields.is_empty() {
+        html.push_str("<dl>");
+        for (key, value) in fields {
+            html.push_str(&format!(
+                "<dt>{}</dt><dd>{}</dd>",
+                escape_html(&key),
+                escape_html(&value)
+            ));
+        }
+        html.push_str("</dl>");
+    }
+    html.push_str("</header>");
+
+    html
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn unescape_html(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+fn percent_encode(text: &str) -> String {
+    text.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Leaves code blocks as they are.
+    fn plain(_: &str, _: &str) -> Option<String> {
+        None
+    }
+
+    #[test]
+    fn test_wiki_links() {
+        let wiki_targets = WikiTargets::new([("Existing.md".to_string(), vec![])]);
+        let html = markdown_to_html(
+            "See [[Existing|this note]] and [[Missing & Co]], not `[[code]]`.",
+            &wiki_targets,
+            &MarkdownOptions::default(),
+            &plain,
+        );
+
+        assert_eq!(
+            html,
+            "<p data-line=\"0\">See <a href=\"wiki:Existing\" class=\"wiki-link\">this note</a> and \
+             <a href=\"wiki:Missing%20%26%20Co\" class=\"wiki-link missing\">Missing &amp; Co</a>, \
+             not <code>[[code]]</code>.</p>"
+        );
+    }
+
+    #[test]
+    fn test_block_lines() {
+        let html = markdown_to_html(
+            "---\ntitle: Lines\n---\n# Heading\n\n- one\n- two\n\n[ref]: /x\n\n<div>\n\n---\n",
+            &WikiTargets::default(),
+            &MarkdownOptions::default(),
+            &plain,
+        );
+
+        assert!(html.starts_with("<header class=\"frontmatter\">"));
+        assert!(html.contains("<h1 data-line=\"3\">Heading</h1>"));
+        assert!(html.contains("<ul data-line=\"5\">\n<li>one</li>"));
+        assert!(html.ends_with("&lt;div&gt;\n<hr data-line=\"12\" />\n"));
+    }
+
+    fn render(text: &str, markdown_options: &MarkdownOptions) -> String {
+        markdown_to_html(text, &WikiTargets::default(), markdown_options, &plain)
+    }
+
+    #[test]
+    fn test_tables() {
+        let html = render(
+            "| a | b |\n| - | :-: |\n| 1 | 2 |\n",
+            &MarkdownOptions::default(),
+        );
+
+        assert!(html.starts_with("<table data-line=\"0\">\n<thead>"));
+        assert!(html.contains("<th align=\"center\">b</th>"));
+        assert!(html.contains("<td>1</td>"));
+    }
+
+    #[test]
+    fn test_task_lists() {
+        let html = render("- [ ] open\n- [x] done\n", &MarkdownOptions::default());
+
+        assert!(html.contains("<li><input type=\"checkbox\" data-offset=\"3\" /> open</li>"));
+        assert!(
+            html.contains(
+                "<li><input type=\"checkbox\" data-offset=\"14\" checked=\"\" /> done</li>"
+            )
+        );
+    }
+
+    #[test]
+    fn test_code_blocks() {
+        let highlight = |language: &str, code: &str| {
+            (language == "rust").then(|| format!("<pre class=\"highlighted\">{}</pre>", code.len()))
+        };
+        let diagram = |language: &str, code: &str| {
+            (language == "dot").then(|| format!("<figure>{}</figure>", escape_html(code.trim())))
+        };
+        let renderers: [&dyn CodeBlockRenderer; 2] = [&diagram, &highlight];
+        let html = markdown_to_html(
+            "```rust title=x\nif a < b {}\n```\n\n    indented\n\n$$\nx\n$$\n\n```dot\na -> b\n```\n",
+            &WikiTargets::default(),
+            &MarkdownOptions::default(),
+            &|language: &str, code: &str| renderers.as_slice().render(language, code),
+        );
+
+        assert_eq!(
+            html,
+            "<div data-line=\"0\" class=\"code-block\"><button class=\"copy-code\" type=\"button\">Copy</button>\
+             <pre class=\"highlighted\">12</pre></div>\n\
+             <div data-line=\"4\" class=\"code-block\"><button class=\"copy-code\" type=\"button\">Copy</button>\
+             <pre><code>indented\n</code></pre></div>\n\
+             <pre data-line=\"6\"><code class=\"language-math math-display\">x\n</code></pre>\n\
+             <figure data-line=\"10\">a -&gt; b</figure>\n"
+        );
+    }
+
+    #[test]
+    fn test_task_offsets() {
+        let text = "---\ntags: [todo]\n---\n1.  [X] nested\n    * [ ] inner\n\n> - [ ] quoted\n\
+                    \n[^1]\n\n[^1]: - [ ] footnote\n";
+        let html = render(text, &MarkdownOptions::default());
+        let offsets: Vec<usize> = html
+            .split("data-offset=\"")
+            .skip(1)
+            .map(|rest| rest[..rest.find('"').unwrap()].parse().unwrap())
+            .collect();
+
+        assert_eq!(offsets.len(), 3);
+        for offset in &offsets {
+            assert_eq!(&text[offset - 1..offset + 2].replace('X', " "), "[ ]");
+        }
+        assert!(html.contains("<input type=\"checkbox\" disabled=\"\" /> footnote"));
+    }
+
+    #[test]
+    fn test_toggle_task() {
+        let text = "- [ ] open\n- [X] done\n";
+
+        assert_eq!(
+            toggle_task(text, 3).as_deref(),
+            Some("- [x] open\n- [X] done\n")
+        );
+        assert_eq!(
+            toggle_task(text, 14).as_deref(),
+            Some("- [ ] open\n- [ ] done\n")
+        );
+        assert_eq!(toggle_task(text, 2), None);
+        assert_eq!(toggle_task(text, 100), None);
+        assert_eq!(toggle_task("[é]", 1), None);
+    }
+
+    #[test]
+    fn test_footnotes() {
+        let html = render("Text[^1].\n\n[^1]: Note.\n", &MarkdownOptions::default());
+
+        assert!(html.starts_with("<p data-line=\"0\">Text<sup><a href=\"#user-content-fn-1\""));
+        assert!(html.contains("<section data-footnotes=\"\" class=\"footnotes\">"));
+        assert!(html.contains("<li id=\"user-content-fn-1\">\n<p>Note. "));
+    }
+
+    #[test]
+    fn test_strikethrough() {
+        let html = render("~~gone~~\n", &MarkdownOptions::default());
+
+        assert_eq!(html, "<p data-line=\"0\"><del>gone</del></p>\n");
+    }
+
+    #[test]
+    fn test_math() {
+        let text = "$$x^2$$ costs $5 and $$\\sum$$\n\n$$\ny < 1\n$$\n";
+        assert_eq!(
+            render(text, &MarkdownOptions::default()),
+            "<p data-line=\"0\"><code class=\"language-math math-inline\">x^2</code> costs $5 and \
+             <code class=\"language-math math-inline\">\\sum</code></p>\n\
+             <pre data-line=\"2\"><code class=\"language-math math-display\">y &lt; 1\n</code></pre>\n"
+        );
+
+        let no_math = MarkdownOptions {
+            math: false,
+            ..MarkdownOptions::default()
+        };
+        assert_eq!(
+            render("$$x^2$$\n", &no_math),
+            "<p data-line=\"0\">$$x^2$$</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_prices_are_not_math() {
+        assert_eq!(
+            render(
+                "It costs $5 and $10, or $x$.\n",
+                &MarkdownOptions::default()
+            ),
+            "<p data-line=\"0\">It costs $5 and $10, or $x$.</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_extensions_off() {
+        let commonmark = MarkdownOptions {
+            gfm: false,
+            frontmatter: false,
+            math: false,
+        };
+
+        assert_eq!(
+            render("- [x] ~~done~~\n", &commonmark),
+            "<ul data-line=\"0\">\n<li>[x] ~~done~~</li>\n</ul>\n"
+        );
+        assert_eq!(
+            render("---\ntitle: T\n---\nBody\n", &commonmark),
+            "<hr data-line=\"0\" />\n<h2 data-line=\"1\">title: T</h2>\n<p data-line=\"3\">Body</p>\n"
+        );
+    }
 }
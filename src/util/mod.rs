@@ -1 +1,5 @@
+pub mod diagrams;
+pub mod diff;
+pub mod highlight;
 pub mod markdown;
+pub mod wiki;
@@ -0,0 +1,120 @@
+use std::collections::HashSet;
+
+/// Splits the text between `[[` and `]]` into the linked note and the label
+/// to show, as in `[[Note Name]]` or `[[Note Name|label]]`.
+pub fn parse_link(inner: &str) -> (&str, &str) {
+    match inner.split_once('|') {
+        Some((target, label)) => (target.trim(), label.trim()),
+        None => (inner.trim(), inner.trim()),
+    }
+}
+
+/// Whether the wiki link `target` points to the note called `name` inside
+/// the collections `path`, e.g. `[[Plan]]` or `[[projects/Plan.md]]`.
+pub fn links_to(target: &str, name: &str, path: &[String]) -> bool {
+    let keys = note_keys(name, path);
+    target_keys(target).iter().any(|key| keys.contains(key))
+}
+
+/// The notes of a notebook, for telling which wiki links point to existing
+/// notes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct WikiTargets {
+    keys: HashSet<String>,
+}
+
+impl WikiTargets {
+    /// Takes the names of all notes, along with the names of the
+    /// collections below the root containing them.
+    pub fn new(notes: impl IntoIterator<Item = (String, Vec<String>)>) -> Self {
+        let keys = notes
+            .into_iter()
+            .flat_map(|(name, path)| note_keys(&name, &path))
+            .collect();
+
+        Self { keys }
+    }
+
+    pub fn contains(&self, target: &str) -> bool {
+        target_keys(target)
+            .iter()
+            .any(|key| self.keys.contains(key))
+    }
+}
+
+/// A note can be linked by its name, or by its name preceded by any number
+/// of the collections containing it.
+fn note_keys(name: &str, path: &[String]) -> Vec<String> {
+    let mut key = strip_extension(name).to_lowercase();
+    let mut keys = vec![key.clone()];
+    for collection in path.iter().rev() {
+        key = format!("{}/{}", collection.to_lowercase(), key);
+        keys.push(key.clone());
+    }
+
+    keys
+}
+
+fn target_keys(target: &str) -> Vec<String> {
+    // links may point to a heading, as in `[[Note#Heading]]`
+    let target = target
+        .split('#')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .trim_matches('/')
+        .to_lowercase();
+    let stripped = strip_extension(&target).to_string();
+
+    if stripped == target {
+        vec![target]
+    } else {
+        vec![target, stripped]
+    }
+}
+
+fn strip_extension(name: &str) -> &str {
+    match name.rsplit_once('.') {
+        Some((stem, extension))
+            if !stem.is_empty() && !stem.ends_with('/') && !extension.contains('/') =>
+        {
+            stem
+        }
+        _ => name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_link() {
+        assert_eq!(parse_link("Note Name"), ("Note Name", "Note Name"));
+        assert_eq!(parse_link("Note | the label"), ("Note", "the label"));
+    }
+
+    #[test]
+    fn test_links_to() {
+        let path = vec!["projects".to_string(), "2024".to_string()];
+
+        assert!(links_to("Plan", "plan.md", &path));
+        assert!(links_to("2024/Plan#Goals", "plan.md", &path));
+        assert!(links_to("projects/2024/plan.md", "plan.md", &path));
+        assert!(!links_to("other/plan", "plan.md", &path));
+        assert!(links_to("v1.2 notes", "v1.2 notes.md", &[]));
+    }
+
+    #[test]
+    fn test_wiki_targets() {
+        let targets = WikiTargets::new([
+            ("Plan.md".to_string(), vec!["projects".to_string()]),
+            ("README".to_string(), vec![]),
+        ]);
+
+        assert!(targets.contains("plan"));
+        assert!(targets.contains("projects/Plan"));
+        assert!(targets.contains("readme"));
+        assert!(!targets.contains("missing"));
+    }
+}